@@ -0,0 +1,159 @@
+// Plays a busy two player game the way the main loop does, updating and
+// drawing every frame, and checks that once it has warmed up it runs without
+// a single allocation. Needs SDL, but no display: it draws to a hidden window
+// with the dummy video driver.
+//
+//   cargo bench --bench game_loop
+
+use sdl2::pixels::Color;
+use sdl2::render::Canvas;
+use sdl2::video::Window;
+use sideways::assets::Assets;
+use sideways::audio::Audio;
+use sideways::difficulty::Difficulty;
+use sideways::handling::Handling;
+use sideways::input::{self, Input};
+use sideways::layers::CollisionMatrix;
+use sideways::particles::ParticleSystem;
+use sideways::starfield::Starfield;
+use sideways::{alloc_count, game, HEIGHT, WIDTH};
+use std::process;
+use std::time::Instant;
+
+#[global_allocator]
+static ALLOCATOR: alloc_count::CountingAllocator = alloc_count::CountingAllocator;
+
+// long enough for the rank to climb and the pools to fill up
+const WARM_UP_FRAMES: u32 = 3_000;
+const FRAMES: u32 = 30_000;
+
+// Both players fire all the time and sweep up and down the screen, so there
+// are always shots, hits, explosions and enemies coming and going
+fn inputs(frame: u32) -> [Input; input::MAX_PLAYERS] {
+    let mut inputs = [Input::default(); input::MAX_PLAYERS];
+    for (i, input) in inputs.iter_mut().enumerate() {
+        let phase = (frame / 40 + i as u32) % 4;
+        input.fire = true;
+        input.up = phase == 0;
+        input.down = phase == 2;
+        input.right = phase == 1;
+        input.left = phase == 3;
+        input.focus = frame % 200 < 50;
+    }
+    inputs
+}
+
+// One pass of the main loop, without the waiting
+fn frame(
+    game: &mut game::Game,
+    frame: u32,
+    audio: &Audio,
+    particles: &mut ParticleSystem,
+    starfield: &mut Starfield,
+    canvas: &mut Canvas<Window>,
+) -> Result<(), String> {
+    starfield.spawn_new_stars();
+    starfield.advance();
+    particles.advance();
+    game.update(&inputs(frame), audio, particles);
+
+    canvas.set_draw_color(Color::RGB(0, 0, 0));
+    canvas.clear();
+    starfield.draw(canvas)?;
+    game.draw(canvas)?;
+    particles.draw(canvas)?;
+    game.draw_hud(canvas)
+}
+
+fn main() -> Result<(), String> {
+    sdl2::hint::set("SDL_VIDEODRIVER", "dummy");
+    let sdl_context = sdl2::init()?;
+    let video = sdl_context.video()?;
+    let window = video
+        .window("game loop", WIDTH, HEIGHT)
+        .hidden()
+        .build()
+        .map_err(|e| e.to_string())?;
+    let mut canvas = window
+        .into_canvas()
+        .software()
+        .build()
+        .map_err(|e| e.to_string())?;
+    let texture_creator = canvas.texture_creator();
+    let mut assets = Assets::new(&texture_creator, Assets::find_dir(None));
+    for name in &["playership", "playershot", "enemy_projectile", "enemyship"] {
+        assets.load_sheet(name)?;
+    }
+    let sprites = game::Sprites {
+        player_ship: assets.sheet("playership"),
+        player_shot: assets.sheet("playershot"),
+        enemy_ship: assets.sheet("enemyship"),
+        enemy_shot: assets.sheet("enemy_projectile"),
+    };
+    let mut particles = ParticleSystem::new(assets.load_texture("particle.png")?);
+    let mut starfield = Starfield::new();
+    let audio = Audio::null();
+
+    let setup = game::Setup {
+        seed: 1,
+        players: input::MAX_PLAYERS,
+        weapon: game::MAX_WEAPON,
+    };
+    let rules = game::Rules {
+        collision_matrix: CollisionMatrix::default(),
+        handling: Handling::default(),
+        difficulty: Difficulty::Hard,
+        rank: true,
+    };
+    let mut game = game::Game::new(sprites, setup, rules);
+    // so the game never ends
+    game.toggle_god();
+
+    for i in 0..WARM_UP_FRAMES {
+        frame(
+            &mut game,
+            i,
+            &audio,
+            &mut particles,
+            &mut starfield,
+            &mut canvas,
+        )?;
+    }
+
+    let allocations = alloc_count::allocations();
+    let start = Instant::now();
+    let (mut enemies, mut projectiles, mut sparks) = (0, 0, 0);
+    for i in WARM_UP_FRAMES..WARM_UP_FRAMES + FRAMES {
+        frame(
+            &mut game,
+            i,
+            &audio,
+            &mut particles,
+            &mut starfield,
+            &mut canvas,
+        )?;
+        let counts = game.counts();
+        enemies = enemies.max(counts.enemies);
+        projectiles = projectiles.max(counts.projectiles);
+        sparks = sparks.max(particles.count());
+    }
+    let elapsed = start.elapsed();
+    let allocations = alloc_count::allocations() - allocations;
+
+    println!(
+        "game loop: {} frames in {:.1} ms, {:.0} us per frame",
+        FRAMES,
+        elapsed.as_secs_f64() * 1000.0,
+        elapsed.as_secs_f64() * 1e6 / f64::from(FRAMES)
+    );
+    println!(
+        "at most {} enemies, {} projectiles and {} particles at once",
+        enemies, projectiles, sparks
+    );
+    println!("allocations after warm-up: {}", allocations);
+    if allocations != 0 {
+        eprintln!("steady state allocated");
+        process::exit(1);
+    }
+    Ok(())
+}
@@ -0,0 +1,99 @@
+// Spawns and despawns pooled items the way a busy game does, and checks that
+// once the pools have warmed up it happens without a single allocation.
+//
+//   cargo bench --bench pool
+
+use sideways::alloc_count;
+use sideways::pool::{Handle, Pool};
+use std::process;
+use std::time::Instant;
+
+#[global_allocator]
+static ALLOCATOR: alloc_count::CountingAllocator = alloc_count::CountingAllocator;
+
+const WARM_UP_FRAMES: usize = 1_000;
+const FRAMES: usize = 100_000;
+
+// About what a projectile or particle weighs
+#[derive(Clone, Copy)]
+struct Item {
+    x: f64,
+    y: f64,
+    vx: f64,
+    vy: f64,
+    life: u32,
+}
+
+// Deterministic stand-in for the game's randomness
+struct Lcg(u64);
+
+impl Lcg {
+    fn next(&mut self) -> u32 {
+        self.0 = self.0.wrapping_mul(6364136223846793005).wrapping_add(1);
+        (self.0 >> 33) as u32
+    }
+}
+
+// One frame of a shooter: a burst of new items, everything moves, expired
+// items are dropped and a few are destroyed by handle, like shots that hit
+fn frame(pool: &mut Pool<Item>, handles: &mut Vec<Handle>, rng: &mut Lcg) {
+    for _ in 0..rng.next() % 16 {
+        if pool.is_full() {
+            break;
+        }
+        let handle = pool.insert(Item {
+            x: 0.0,
+            y: 0.0,
+            vx: 4.0,
+            vy: 1.0,
+            life: 20 + rng.next() % 40,
+        });
+        if handles.len() < handles.capacity() {
+            handles.push(handle);
+        }
+    }
+    pool.retain(|item| {
+        item.x += item.vx;
+        item.y += item.vy;
+        item.life = item.life.saturating_sub(1);
+        item.life > 0
+    });
+    for _ in 0..rng.next() % 4 {
+        if handles.is_empty() {
+            break;
+        }
+        let i = rng.next() as usize % handles.len();
+        // may already have expired; then the stale handle finds nothing
+        pool.remove(handles.swap_remove(i));
+    }
+}
+
+fn main() {
+    let mut pool = Pool::with_capacity(1024);
+    let mut handles = Vec::with_capacity(256);
+    let mut rng = Lcg(1);
+    for _ in 0..WARM_UP_FRAMES {
+        frame(&mut pool, &mut handles, &mut rng);
+    }
+
+    let allocations = alloc_count::allocations();
+    let start = Instant::now();
+    for _ in 0..FRAMES {
+        frame(&mut pool, &mut handles, &mut rng);
+    }
+    let elapsed = start.elapsed();
+    let allocations = alloc_count::allocations() - allocations;
+
+    println!(
+        "pool churn: {} frames in {:.1} ms, {:.0} ns per frame, {} items alive",
+        FRAMES,
+        elapsed.as_secs_f64() * 1000.0,
+        elapsed.as_secs_f64() * 1e9 / FRAMES as f64,
+        pool.len()
+    );
+    println!("allocations after warm-up: {}", allocations);
+    if allocations != 0 {
+        eprintln!("steady state allocated");
+        process::exit(1);
+    }
+}
@@ -0,0 +1,44 @@
+use std::env;
+use std::fs;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+// With the embed-assets feature, generate a table of every file under assets/
+// for src/embedded.rs to include, so the game can run from a single binary.
+fn main() {
+    println!("cargo:rerun-if-changed=assets");
+    if env::var_os("CARGO_FEATURE_EMBED_ASSETS").is_none() {
+        return;
+    }
+
+    let root = Path::new(&env::var("CARGO_MANIFEST_DIR").unwrap()).join("assets");
+    let mut files = vec![];
+    collect_files(&root, &mut files);
+    files.sort();
+
+    let out = Path::new(&env::var("OUT_DIR").unwrap()).join("embedded_assets.rs");
+    let mut out = fs::File::create(out).unwrap();
+    writeln!(out, "pub static FILES: &[(&str, &[u8])] = &[").unwrap();
+    for file in files {
+        let name = file
+            .strip_prefix(&root)
+            .unwrap()
+            .components()
+            .map(|x| x.as_os_str().to_string_lossy().into_owned())
+            .collect::<Vec<_>>()
+            .join("/");
+        writeln!(out, "    ({:?}, include_bytes!({:?})),", name, file).unwrap();
+    }
+    writeln!(out, "];").unwrap();
+}
+
+fn collect_files(dir: &Path, files: &mut Vec<PathBuf>) {
+    for entry in fs::read_dir(dir).unwrap() {
+        let path = entry.unwrap().path();
+        if path.is_dir() {
+            collect_files(&path, files);
+        } else {
+            files.push(path);
+        }
+    }
+}
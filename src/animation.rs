@@ -0,0 +1,270 @@
+use sdl2::pixels::Color;
+use sdl2::rect::Rect;
+use sdl2::render::{Canvas, Texture};
+use sdl2::video::Window;
+use std::cell::{Ref, RefCell};
+use std::collections::HashMap;
+use std::time::Duration;
+
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum LoopMode {
+    Loop,
+    // stop on the last frame
+    Once,
+    // play forwards, then backwards, and so on
+    PingPong,
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Frame {
+    rect: Rect,
+    duration: Duration,
+}
+
+#[derive(Debug, Clone)]
+struct Animation {
+    name: String,
+    frames: Vec<Frame>,
+    mode: LoopMode,
+}
+
+// Frame rectangles and named animations for one sprite sheet image.
+//
+// Atlas files are plain text, one definition per line:
+//
+//   frame <name> <x> <y> <width> <height>
+//   anim <name> <loop|once|pingpong> <frame>:<milliseconds> ...
+//
+// Empty lines and lines starting with # are ignored.
+#[derive(Debug, Clone)]
+pub struct Atlas {
+    animations: Vec<Animation>,
+}
+
+impl Atlas {
+    pub fn parse(source: &str) -> Result<Atlas, String> {
+        let mut frames: HashMap<&str, Rect> = HashMap::new();
+        let mut animations = vec![];
+        for (line_no, line) in source.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let err = |msg: &str| format!("line {}: {}", line_no + 1, msg);
+            let words: Vec<&str> = line.split_whitespace().collect();
+            match words[0] {
+                "frame" => {
+                    if words.len() != 6 {
+                        return Err(err("expected frame <name> <x> <y> <width> <height>"));
+                    }
+                    let num = |s: &str| s.parse::<i32>().map_err(|_| err("invalid number"));
+                    let (w, h) = (num(words[4])?, num(words[5])?);
+                    if w <= 0 || h <= 0 {
+                        return Err(err("frame size must be positive"));
+                    }
+                    frames.insert(
+                        words[1],
+                        Rect::new(num(words[2])?, num(words[3])?, w as u32, h as u32),
+                    );
+                }
+                "anim" => {
+                    if words.len() < 4 {
+                        return Err(err("expected anim <name> <mode> <frame>:<ms> ..."));
+                    }
+                    let mode = match words[2] {
+                        "loop" => LoopMode::Loop,
+                        "once" => LoopMode::Once,
+                        "pingpong" => LoopMode::PingPong,
+                        _ => return Err(err("loop mode must be loop, once or pingpong")),
+                    };
+                    let mut anim_frames = vec![];
+                    for word in &words[3..] {
+                        let mut parts = word.splitn(2, ':');
+                        let frame_name = parts.next().unwrap_or("");
+                        let rect = *frames
+                            .get(frame_name)
+                            .ok_or_else(|| err(&format!("unknown frame {}", frame_name)))?;
+                        let ms = parts
+                            .next()
+                            .and_then(|x| x.parse::<u64>().ok())
+                            .filter(|&x| x > 0)
+                            .ok_or_else(|| err("frame duration must be a positive number"))?;
+                        anim_frames.push(Frame {
+                            rect,
+                            duration: Duration::from_millis(ms),
+                        });
+                    }
+                    animations.push(Animation {
+                        name: words[1].to_string(),
+                        frames: anim_frames,
+                        mode,
+                    });
+                }
+                other => return Err(err(&format!("unknown definition {}", other))),
+            }
+        }
+        if animations.is_empty() {
+            return Err("atlas has no animations".to_string());
+        }
+        Ok(Atlas { animations })
+    }
+
+    // Index of the named animation, or the first animation if there's no such name
+    fn find(&self, name: &str) -> usize {
+        self.animations
+            .iter()
+            .position(|x| x.name == name)
+            .unwrap_or(0)
+    }
+
+    // Size of the first frame of the named animation
+    pub fn frame_size(&self, name: &str) -> (u32, u32) {
+        let rect = self.animations[self.find(name)].frames[0].rect;
+        (rect.width(), rect.height())
+    }
+}
+
+// A texture together with the atlas describing its frames.
+// Both can be swapped out while entities hold on to the sheet,
+// which is how the asset manager hot-reloads them.
+pub struct SpriteSheet<'b> {
+    texture: RefCell<Texture<'b>>,
+    atlas: RefCell<Atlas>,
+}
+
+impl<'b> SpriteSheet<'b> {
+    pub fn new(texture: Texture<'b>, atlas: Atlas) -> Self {
+        SpriteSheet {
+            texture: RefCell::new(texture),
+            atlas: RefCell::new(atlas),
+        }
+    }
+
+    pub fn atlas(&self) -> Ref<'_, Atlas> {
+        self.atlas.borrow()
+    }
+
+    pub fn replace_texture(&self, texture: Texture<'b>) {
+        *self.texture.borrow_mut() = texture;
+    }
+
+    pub fn replace_atlas(&self, atlas: Atlas) {
+        *self.atlas.borrow_mut() = atlas;
+    }
+
+    pub fn draw(
+        &self,
+        canvas: &mut Canvas<Window>,
+        player: &AnimationPlayer,
+        dest: Rect,
+    ) -> Result<(), String> {
+        canvas.copy(&self.texture.borrow(), player.frame(&self.atlas()), dest)
+    }
+
+    // Draw turned clockwise by angle radians around the middle of dest
+    pub fn draw_rotated(
+        &self,
+        canvas: &mut Canvas<Window>,
+        player: &AnimationPlayer,
+        dest: Rect,
+        angle: f64,
+    ) -> Result<(), String> {
+        canvas.copy_ex(
+            &self.texture.borrow(),
+            player.frame(&self.atlas()),
+            dest,
+            angle.to_degrees(),
+            None,
+            false,
+            false,
+        )
+    }
+
+    // Multiply the colours of everything drawn from now on, white to undo
+    pub fn set_tint(&self, color: Color) {
+        self.texture
+            .borrow_mut()
+            .set_color_mod(color.r, color.g, color.b);
+    }
+}
+
+// Keeps track of which frame of which animation an entity is showing
+#[derive(Debug, Clone, Copy)]
+pub struct AnimationPlayer {
+    animation: usize,
+    frame: usize,
+    elapsed: Duration,
+    backwards: bool,
+}
+
+impl AnimationPlayer {
+    pub fn new(atlas: &Atlas, name: &str) -> Self {
+        AnimationPlayer {
+            animation: atlas.find(name),
+            frame: 0,
+            elapsed: Duration::from_secs(0),
+            backwards: false,
+        }
+    }
+
+    // Switch to the named animation, restarting it unless it's already playing
+    pub fn play(&mut self, atlas: &Atlas, name: &str) {
+        let animation = atlas.find(name);
+        if animation != self.animation {
+            *self = AnimationPlayer {
+                animation,
+                frame: 0,
+                elapsed: Duration::from_secs(0),
+                backwards: false,
+            };
+        }
+    }
+
+    pub fn advance(&mut self, atlas: &Atlas, dt: Duration) {
+        // the atlas may have been reloaded with fewer animations or frames
+        if self.animation >= atlas.animations.len()
+            || self.frame >= atlas.animations[self.animation].frames.len()
+        {
+            *self = AnimationPlayer::new(atlas, "");
+        }
+        let anim = &atlas.animations[self.animation];
+        let last = anim.frames.len() - 1;
+        self.elapsed += dt;
+        while self.elapsed >= anim.frames[self.frame].duration {
+            self.elapsed -= anim.frames[self.frame].duration;
+            match anim.mode {
+                LoopMode::Loop => self.frame = (self.frame + 1) % (last + 1),
+                LoopMode::Once => {
+                    if self.frame == last {
+                        self.elapsed = Duration::from_secs(0);
+                        return;
+                    }
+                    self.frame += 1;
+                }
+                LoopMode::PingPong => {
+                    if last == 0 {
+                        continue;
+                    }
+                    if self.backwards && self.frame == 0 {
+                        self.backwards = false;
+                    } else if !self.backwards && self.frame == last {
+                        self.backwards = true;
+                    }
+                    if self.backwards {
+                        self.frame -= 1;
+                    } else {
+                        self.frame += 1;
+                    }
+                }
+            }
+        }
+    }
+
+    pub fn frame(&self, atlas: &Atlas) -> Rect {
+        let anim = atlas
+            .animations
+            .get(self.animation)
+            .unwrap_or(&atlas.animations[0]);
+        anim.frames.get(self.frame).unwrap_or(&anim.frames[0]).rect
+    }
+}
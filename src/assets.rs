@@ -0,0 +1,198 @@
+use crate::animation::{Atlas, SpriteSheet};
+#[cfg(feature = "embed-assets")]
+use crate::embedded;
+use sdl2::image::{ImageRWops, LoadTexture};
+use sdl2::mixer::{Chunk, LoaderRWops, Music};
+use sdl2::render::{Texture, TextureCreator};
+use sdl2::rwops::RWops;
+use sdl2::video::WindowContext;
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::env;
+use std::fs;
+use std::path::PathBuf;
+use std::time::SystemTime;
+
+// Environment variable overriding where the assets are read from
+const ASSETS_DIR_ENV: &str = "SIDEWAYS_ASSETS";
+
+// Where the data for an asset comes from
+enum Source {
+    // compiled into the binary with the embed-assets feature
+    #[cfg_attr(not(feature = "embed-assets"), allow(dead_code))]
+    Embedded(&'static [u8]),
+    Disk(PathBuf),
+}
+
+// Finds, loads and caches game assets by name, relative to the asset directory.
+// With the embed-assets feature, files compiled into the binary are used
+// instead of the ones on disk.
+//
+// Sprite sheets are loaded up front with load_sheet() and then shared by
+// reference. In debug builds hot_reload() swaps in any sheet whose image or
+// atlas has changed on disk, and reports other changed data files so their
+// owners can reload them.
+pub struct Assets<'b> {
+    dir: PathBuf,
+    texture_creator: &'b TextureCreator<WindowContext>,
+    sheets: HashMap<String, SpriteSheet<'b>>,
+    // modification times of every file read so far
+    watched: RefCell<HashMap<String, Option<SystemTime>>>,
+}
+
+impl<'b> Assets<'b> {
+    pub fn new(texture_creator: &'b TextureCreator<WindowContext>, dir: PathBuf) -> Self {
+        Assets {
+            dir,
+            texture_creator,
+            sheets: HashMap::new(),
+            watched: RefCell::new(HashMap::new()),
+        }
+    }
+
+    // Work out where the assets are. In order of preference:
+    // the command line flag, the SIDEWAYS_ASSETS environment variable,
+    // an assets directory next to the executable, or one in the
+    // current working directory.
+    pub fn find_dir(flag: Option<PathBuf>) -> PathBuf {
+        if let Some(dir) = flag {
+            return dir;
+        }
+        if let Some(dir) = env::var_os(ASSETS_DIR_ENV) {
+            return PathBuf::from(dir);
+        }
+        if let Ok(exe) = env::current_exe() {
+            if let Some(dir) = exe.parent().map(|x| x.join("assets")) {
+                if dir.is_dir() {
+                    return dir;
+                }
+            }
+        }
+        PathBuf::from("assets")
+    }
+
+    fn source(&self, name: &str) -> Source {
+        #[cfg(feature = "embed-assets")]
+        {
+            if let Some(data) = embedded::get(name) {
+                return Source::Embedded(data);
+            }
+        }
+        Source::Disk(self.dir.join(name))
+    }
+
+    fn modified(&self, name: &str) -> Option<SystemTime> {
+        fs::metadata(self.dir.join(name))
+            .and_then(|x| x.modified())
+            .ok()
+    }
+
+    fn watch(&self, name: &str) {
+        // embedded files can't change
+        if let Source::Disk(_) = self.source(name) {
+            let modified = self.modified(name);
+            self.watched.borrow_mut().insert(name.to_string(), modified);
+        }
+    }
+
+    pub fn read_to_string(&self, name: &str) -> Result<String, String> {
+        self.watch(name);
+        let result = match self.source(name) {
+            Source::Embedded(data) => String::from_utf8(data.to_vec()).map_err(|e| e.to_string()),
+            Source::Disk(path) => fs::read_to_string(path).map_err(|e| e.to_string()),
+        };
+        result.map_err(|e| format!("{}: {}", name, e))
+    }
+
+    // Load a texture that isn't cached; the caller owns it
+    pub fn load_texture(&self, name: &str) -> Result<Texture<'b>, String> {
+        self.watch(name);
+        let result = match self.source(name) {
+            Source::Embedded(data) => {
+                let rwops = RWops::from_bytes(data)?;
+                let surface = rwops.load()?;
+                self.texture_creator
+                    .create_texture_from_surface(surface)
+                    .map_err(|e| e.to_string())
+            }
+            Source::Disk(path) => self.texture_creator.load_texture(path),
+        };
+        result.map_err(|e| format!("{}: {}", name, e))
+    }
+
+    pub fn load_sound(&self, name: &str) -> Result<Chunk, String> {
+        let result = match self.source(name) {
+            Source::Embedded(data) => RWops::from_bytes(data)?.load_wav(),
+            Source::Disk(path) => Chunk::from_file(path),
+        };
+        result.map_err(|e| format!("{}: {}", name, e))
+    }
+
+    pub fn load_music(&self, name: &str) -> Result<Music<'static>, String> {
+        let result = match self.source(name) {
+            Source::Embedded(data) => Music::from_static_bytes(data),
+            Source::Disk(path) => Music::from_file(path),
+        };
+        result.map_err(|e| format!("{}: {}", name, e))
+    }
+
+    fn load_atlas(&self, name: &str) -> Result<Atlas, String> {
+        let source = self.read_to_string(name)?;
+        Atlas::parse(&source).map_err(|e| format!("{}: {}", name, e))
+    }
+
+    // Load <name>.png and <name>.atlas into the cache
+    pub fn load_sheet(&mut self, name: &str) -> Result<(), String> {
+        let sheet = SpriteSheet::new(
+            self.load_texture(&format!("{}.png", name))?,
+            self.load_atlas(&format!("{}.atlas", name))?,
+        );
+        self.sheets.insert(name.to_string(), sheet);
+        Ok(())
+    }
+
+    // Panics if the sheet hasn't been loaded, which is a programming error
+    pub fn sheet(&self, name: &str) -> &SpriteSheet<'b> {
+        self.sheets
+            .get(name)
+            .unwrap_or_else(|| panic!("sprite sheet {} was never loaded", name))
+    }
+
+    // Reload cached sprite sheets whose files changed since they were read.
+    // Returns the names of the other changed files, whose owners need to
+    // reload them themselves.
+    pub fn hot_reload(&self) -> Vec<String> {
+        let changed: Vec<String> = self
+            .watched
+            .borrow()
+            .iter()
+            .filter(|(name, modified)| self.modified(name) != **modified)
+            .map(|(name, _)| name.clone())
+            .collect();
+
+        let mut others = vec![];
+        for name in changed {
+            // remember the new time even if reloading fails, so a broken
+            // file is reported once instead of every time we check
+            self.watch(&name);
+            let sheet_name = name.trim_end_matches(".png").trim_end_matches(".atlas");
+            let sheet = match self.sheets.get(sheet_name) {
+                Some(sheet) => sheet,
+                None => {
+                    others.push(name);
+                    continue;
+                }
+            };
+            let result = if name.ends_with(".png") {
+                self.load_texture(&name).map(|x| sheet.replace_texture(x))
+            } else {
+                self.load_atlas(&name).map(|x| sheet.replace_atlas(x))
+            };
+            match result {
+                Ok(()) => eprintln!("Reloaded {}", name),
+                Err(e) => eprintln!("Could not reload {}: {}", name, e),
+            }
+        }
+        others
+    }
+}
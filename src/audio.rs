@@ -0,0 +1,204 @@
+use crate::assets::Assets;
+use sdl2::mixer::{self, Channel, Chunk, Music};
+use sdl2::{AudioSubsystem, Sdl};
+use std::collections::HashMap;
+
+pub const MAX_VOLUME: u8 = 10;
+pub const DEFAULT_SFX_VOLUME: u8 = 8;
+pub const DEFAULT_MUSIC_VOLUME: u8 = 6;
+const MIXER_CHANNELS: i32 = 16;
+
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Hash)]
+pub enum Sound {
+    Shoot,
+    EnemyShoot,
+    Hit,
+    Explosion,
+    MenuMove,
+    MenuSelect,
+    PowerUp,
+}
+
+impl Sound {
+    const ALL: [Sound; 7] = [
+        Sound::Shoot,
+        Sound::EnemyShoot,
+        Sound::Hit,
+        Sound::Explosion,
+        Sound::MenuMove,
+        Sound::MenuSelect,
+        Sound::PowerUp,
+    ];
+
+    fn file_name(self) -> &'static str {
+        match self {
+            Sound::Shoot => "shoot.wav",
+            Sound::EnemyShoot => "enemy_shoot.wav",
+            Sound::Hit => "hit.wav",
+            Sound::Explosion => "explosion.wav",
+            Sound::MenuMove => "menu_move.wav",
+            Sound::MenuSelect => "menu_select.wav",
+            Sound::PowerUp => "powerup.wav",
+        }
+    }
+}
+
+enum Backend {
+    // No sound device, or the mixer couldn't be opened: every call is a no-op
+    Null,
+    Mixer {
+        _subsystem: AudioSubsystem,
+        chunks: HashMap<Sound, Chunk>,
+        music: Option<(String, Music<'static>)>,
+    },
+}
+
+pub struct Audio {
+    backend: Backend,
+    sfx_volume: u8,
+    music_volume: u8,
+}
+
+impl Audio {
+    // Open the default audio device, falling back to silence if that fails
+    pub fn new(sdl_context: &Sdl, assets: &Assets) -> Self {
+        match Audio::open_mixer(sdl_context, assets) {
+            Ok(backend) => {
+                let mut audio = Audio {
+                    backend,
+                    sfx_volume: DEFAULT_SFX_VOLUME,
+                    music_volume: DEFAULT_MUSIC_VOLUME,
+                };
+                audio.apply_volumes();
+                audio
+            }
+            Err(e) => {
+                eprintln!("Could not open audio device, sound disabled: {}", e);
+                Audio::null()
+            }
+        }
+    }
+
+    pub fn null() -> Self {
+        Audio {
+            backend: Backend::Null,
+            sfx_volume: DEFAULT_SFX_VOLUME,
+            music_volume: DEFAULT_MUSIC_VOLUME,
+        }
+    }
+
+    fn open_mixer(sdl_context: &Sdl, assets: &Assets) -> Result<Backend, String> {
+        let subsystem = sdl_context.audio()?;
+        mixer::open_audio(
+            mixer::DEFAULT_FREQUENCY,
+            mixer::DEFAULT_FORMAT,
+            mixer::DEFAULT_CHANNELS,
+            1024,
+        )?;
+        mixer::allocate_channels(MIXER_CHANNELS);
+
+        let mut chunks = HashMap::new();
+        for sound in Sound::ALL.iter() {
+            // a missing sound effect shouldn't keep the game from running
+            match assets.load_sound(&format!("sounds/{}", sound.file_name())) {
+                Ok(chunk) => {
+                    chunks.insert(*sound, chunk);
+                }
+                Err(e) => eprintln!("Could not load sound: {}", e),
+            }
+        }
+        Ok(Backend::Mixer {
+            _subsystem: subsystem,
+            chunks,
+            music: None,
+        })
+    }
+
+    pub fn play(&self, sound: Sound) {
+        if let Backend::Mixer { chunks, .. } = &self.backend {
+            if let Some(chunk) = chunks.get(&sound) {
+                // all channels busy is fine, the sound just gets dropped
+                let _ = Channel::all().play(chunk, 0);
+            }
+        }
+    }
+
+    // Start looping the named track from music/, unless it's already playing
+    pub fn play_music(&mut self, assets: &Assets, track: &str) {
+        if let Backend::Mixer { music, .. } = &mut self.backend {
+            if let Some((name, _)) = music {
+                if name == track {
+                    if Music::is_paused() {
+                        Music::resume();
+                    }
+                    return;
+                }
+            }
+            match assets.load_music(&format!("music/{}.wav", track)) {
+                Ok(m) => {
+                    if let Err(e) = m.play(-1) {
+                        eprintln!("Could not play music {}: {}", track, e);
+                    }
+                    *music = Some((track.to_string(), m));
+                }
+                Err(e) => eprintln!("Could not load music: {}", e),
+            }
+        }
+    }
+
+    pub fn pause_music(&self) {
+        if let Backend::Mixer { .. } = self.backend {
+            Music::pause();
+        }
+    }
+
+    pub fn resume_music(&self) {
+        if let Backend::Mixer { .. } = self.backend {
+            Music::resume();
+        }
+    }
+
+    pub fn stop_music(&mut self) {
+        if let Backend::Mixer { music, .. } = &mut self.backend {
+            Music::halt();
+            *music = None;
+        }
+    }
+
+    pub fn sfx_volume(&self) -> u8 {
+        self.sfx_volume
+    }
+
+    pub fn music_volume(&self) -> u8 {
+        self.music_volume
+    }
+
+    pub fn set_sfx_volume(&mut self, volume: u8) {
+        self.sfx_volume = volume.min(MAX_VOLUME);
+        self.apply_volumes();
+    }
+
+    pub fn set_music_volume(&mut self, volume: u8) {
+        self.music_volume = volume.min(MAX_VOLUME);
+        self.apply_volumes();
+    }
+
+    fn apply_volumes(&mut self) {
+        if let Backend::Mixer { .. } = self.backend {
+            let to_mixer = |v: u8| i32::from(v) * mixer::MAX_VOLUME / i32::from(MAX_VOLUME);
+            Channel::all().set_volume(to_mixer(self.sfx_volume));
+            Music::set_volume(to_mixer(self.music_volume));
+        }
+    }
+}
+
+impl Drop for Audio {
+    fn drop(&mut self) {
+        if let Backend::Mixer { chunks, music, .. } = &mut self.backend {
+            // sounds must be freed before the device is closed
+            chunks.clear();
+            *music = None;
+            mixer::close_audio();
+        }
+    }
+}
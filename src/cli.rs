@@ -0,0 +1,248 @@
+use crate::display::{WindowMode, MAX_SCALE};
+use crate::net::{Conditions, Role};
+use crate::rollback;
+use std::path::PathBuf;
+use std::str::FromStr;
+use std::time::Duration;
+
+pub const USAGE: &str = "\
+Usage: sideways [options]
+
+Display:
+  --scale <n>          window size, in multiples of the game's resolution
+  --fullscreen         start fullscreen
+  --windowed           start in a window
+
+Starting a game straight away, skipping the title screen:
+  --seed <n>           with this seed
+  --level <name>       in this level, whether it's unlocked or not. Also picks the
+                       level when hosting a network game
+  --god                with the players invincible
+  --replay <file>      playing back a recorded game
+  --record <file>      record the next game played to a file
+  --headless           without a window or sound, as fast as possible, printing
+                       the scores at the end
+  --frames <n>         quit after this many frames, needed for --headless with
+                       --god
+
+Network games:
+  --host <port>        host a game for another player to join
+  --join <address>     join a game at host:port
+  --input-delay <n>    frames both players' input is held back, when hosting
+  --latency <ms>       hold back every packet sent, for testing
+  --loss <percent>     drop that many packets, for testing
+
+  --assets-dir <dir>   where to find the game's assets
+  --help               show this
+";
+
+// What the game was started with
+pub struct Options {
+    pub help: bool,
+    pub assets_dir: Option<PathBuf>,
+    pub scale: Option<u32>,
+    pub mode: Option<WindowMode>,
+    pub seed: Option<u64>,
+    pub level: Option<String>,
+    pub god: bool,
+    pub replay: Option<PathBuf>,
+    pub record: Option<PathBuf>,
+    pub headless: bool,
+    pub frames: Option<u32>,
+    pub role: Option<Role>,
+    pub conditions: Conditions,
+    pub input_delay: u32,
+}
+
+// The value after a flag
+fn flag_value<T: FromStr>(
+    args: &mut impl Iterator<Item = String>,
+    flag: &str,
+) -> Result<T, String> {
+    let value = args
+        .next()
+        .ok_or_else(|| format!("{} needs a value", flag))?;
+    value
+        .parse()
+        .map_err(|_| format!("{}: bad value {}", flag, value))
+}
+
+impl Options {
+    pub fn parse(mut args: impl Iterator<Item = String>) -> Result<Options, String> {
+        let mut options = Options {
+            help: false,
+            assets_dir: None,
+            scale: None,
+            mode: None,
+            seed: None,
+            level: None,
+            god: false,
+            replay: None,
+            record: None,
+            headless: false,
+            frames: None,
+            role: None,
+            conditions: Conditions::default(),
+            input_delay: rollback::DEFAULT_INPUT_DELAY,
+        };
+        // the host decides the input delay for both players
+        let mut delay_given = false;
+        while let Some(arg) = args.next() {
+            match arg.as_str() {
+                "--help" | "-h" => options.help = true,
+                "--assets-dir" => options.assets_dir = Some(flag_value(&mut args, &arg)?),
+                "--scale" => match flag_value(&mut args, &arg)? {
+                    scale if (1..=MAX_SCALE).contains(&scale) => options.scale = Some(scale),
+                    _ => return Err(format!("--scale goes from 1 to {}", MAX_SCALE)),
+                },
+                "--fullscreen" => options.mode = Some(WindowMode::Fullscreen),
+                "--windowed" => options.mode = Some(WindowMode::Windowed),
+                "--seed" => options.seed = Some(flag_value(&mut args, &arg)?),
+                "--level" => options.level = Some(flag_value(&mut args, &arg)?),
+                "--god" => options.god = true,
+                "--replay" => options.replay = Some(flag_value(&mut args, &arg)?),
+                "--record" => options.record = Some(flag_value(&mut args, &arg)?),
+                "--headless" => options.headless = true,
+                "--frames" => match flag_value(&mut args, &arg)? {
+                    0 => return Err("--frames has to be at least 1".to_string()),
+                    frames => options.frames = Some(frames),
+                },
+                "--host" => options.role = Some(Role::Host(flag_value(&mut args, &arg)?)),
+                "--join" => options.role = Some(Role::Join(flag_value(&mut args, &arg)?)),
+                // simulated network trouble, for testing network games
+                "--latency" => {
+                    options.conditions.latency = Duration::from_millis(flag_value(&mut args, &arg)?)
+                }
+                "--loss" => {
+                    let percent: f64 = flag_value(&mut args, &arg)?;
+                    options.conditions.loss = (percent / 100.0).clamp(0.0, 1.0);
+                }
+                "--input-delay" => {
+                    options.input_delay = flag_value(&mut args, &arg)?;
+                    delay_given = true;
+                    if options.input_delay > rollback::MAX_INPUT_DELAY {
+                        return Err(format!(
+                            "--input-delay goes from 0 to {}",
+                            rollback::MAX_INPUT_DELAY
+                        ));
+                    }
+                }
+                _ => return Err(format!("unknown argument {}, see --help", arg)),
+            }
+        }
+
+        if options.role.is_some()
+            && (options.seed.is_some()
+                || options.god
+                || options.replay.is_some()
+                || options.headless
+                || options.record.is_some())
+        {
+            return Err("a network game can't be recorded, replayed or started with --seed, --god or --headless".to_string());
+        }
+        if options.level.is_some() && matches!(options.role, Some(Role::Join(_))) {
+            return Err("--level is for hosting, whoever joins plays the host's".to_string());
+        }
+        if delay_given && !matches!(options.role, Some(Role::Host(_))) {
+            return Err("--input-delay is for hosting, whoever joins uses the host's".to_string());
+        }
+        if options.replay.is_some()
+            && (options.seed.is_some() || options.level.is_some() || options.god)
+        {
+            return Err(
+                "a replay plays with the seed, level and god mode it was recorded with".to_string(),
+            );
+        }
+        // an invincible player's game never ends by itself
+        if options.headless && options.god && options.frames.is_none() {
+            return Err("--headless with --god needs --frames".to_string());
+        }
+        Ok(options)
+    }
+
+    // Whether to skip the title screen and start playing. A network game
+    // starts once the other player is there instead.
+    pub fn starts_game(&self) -> bool {
+        self.role.is_none()
+            && (self.seed.is_some()
+                || self.level.is_some()
+                || self.god
+                || self.replay.is_some()
+                || self.headless)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(args: &str) -> Result<Options, String> {
+        Options::parse(args.split_whitespace().map(|x| x.to_string()))
+    }
+
+    fn error(args: &str) -> String {
+        parse(args).err().expect("should have been refused")
+    }
+
+    #[test]
+    fn nothing() {
+        let options = parse("").unwrap();
+        assert!(!options.help);
+        assert!(!options.starts_game());
+        assert_eq!(options.role, None);
+        assert_eq!(options.input_delay, rollback::DEFAULT_INPUT_DELAY);
+    }
+
+    #[test]
+    fn starting_a_game() {
+        let options = parse("--seed 12 --level level2 --god --record run.txt").unwrap();
+        assert_eq!(options.seed, Some(12));
+        assert_eq!(options.level.as_deref(), Some("level2"));
+        assert!(options.god);
+        assert_eq!(options.record, Some(PathBuf::from("run.txt")));
+        assert!(options.starts_game());
+        assert!(parse("--headless").unwrap().starts_game());
+        assert!(parse("--replay run.txt").unwrap().starts_game());
+        assert!(!parse("--record run.txt").unwrap().starts_game());
+    }
+
+    #[test]
+    fn network() {
+        let options = parse("--host 7000 --input-delay 4 --latency 100 --loss 250").unwrap();
+        assert_eq!(options.role, Some(Role::Host(7000)));
+        assert_eq!(options.input_delay, 4);
+        assert_eq!(options.conditions.latency, Duration::from_millis(100));
+        assert_eq!(options.conditions.loss, 1.0);
+        let options = parse("--join 127.0.0.1:7000").unwrap();
+        assert_eq!(options.role, Some(Role::Join("127.0.0.1:7000".to_string())));
+        // the host picks the level, and both wait for each other
+        let options = parse("--host 7000 --level level2").unwrap();
+        assert_eq!(options.level.as_deref(), Some("level2"));
+        assert!(!options.starts_game());
+    }
+
+    #[test]
+    fn bad_values() {
+        assert_eq!(error("--scale"), "--scale needs a value");
+        assert_eq!(error("--seed twelve"), "--seed: bad value twelve");
+        assert!(error(&format!("--scale {}", MAX_SCALE + 1)).starts_with("--scale goes from"));
+        assert!(error("--host 7000 --input-delay 99").starts_with("--input-delay goes from"));
+        assert_eq!(error("--frames 0"), "--frames has to be at least 1");
+        assert_eq!(error("--fast"), "unknown argument --fast, see --help");
+    }
+
+    #[test]
+    fn conflicts() {
+        assert!(error("--host 7000 --record run.txt").starts_with("a network game can't"));
+        assert!(error("--join a:1 --god").starts_with("a network game can't"));
+        assert!(error("--join a:1 --level level2").starts_with("--level is for hosting"));
+        assert!(error("--join a:1 --input-delay 3").starts_with("--input-delay is for hosting"));
+        assert!(error("--input-delay 3").starts_with("--input-delay is for hosting"));
+        assert!(error("--replay run.txt --seed 3").starts_with("a replay plays with"));
+        assert_eq!(
+            error("--headless --god"),
+            "--headless with --god needs --frames"
+        );
+        assert!(parse("--headless --god --frames 100").is_ok());
+    }
+}
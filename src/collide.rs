@@ -1,16 +1,18 @@
-use crate::Velocity;
-use crate::{enemy, player, projectile};
+use crate::vec2::Vec2;
 use crate::{HEIGHT, WIDTH};
+use std::f64::consts::FRAC_PI_2;
 
-#[derive(Eq, PartialEq, Debug)]
+#[derive(PartialEq, Clone, Debug)]
 pub enum Shape {
     Point(Point),
     Circle(Circle),
     Rectangle(Rectangle),
+    OrientedRect(OrientedRect),
+    Capsule(Capsule),
     Compound(Vec<Shape>),
 }
 
-#[derive(Eq, PartialEq, Debug)]
+#[derive(Eq, PartialEq, Clone, Copy, Debug)]
 pub struct Point {
     pub x: i32,
     pub y: i32,
@@ -22,12 +24,51 @@ pub struct Rectangle {
     pub width: u32,
     pub height: u32,
 }
-#[derive(Eq, PartialEq, Debug)]
+#[derive(Eq, PartialEq, Clone, Copy, Debug)]
 pub struct Circle {
     pub x: i32,
     pub y: i32,
     pub r: u32,
 }
+// Rectangle turned around its centre by angle radians, clockwise on screen
+#[derive(PartialEq, Clone, Copy, Debug)]
+pub struct OrientedRect {
+    pub center: Vec2,
+    pub width: u32,
+    pub height: u32,
+    pub angle: f64,
+}
+// Everything within r of a line segment through the centre, length long and
+// turned by angle radians. A rectangle with round ends, like a laser beam.
+#[derive(PartialEq, Clone, Copy, Debug)]
+pub struct Capsule {
+    pub center: Vec2,
+    pub length: u32,
+    pub r: u32,
+    pub angle: f64,
+}
+
+impl OrientedRect {
+    // Corners, going around the rectangle
+    pub fn corners(&self) -> [Vec2; 4] {
+        let along = Vec2::from_angle(self.angle) * (self.width as f64 / 2.0);
+        let across = Vec2::from_angle(self.angle).rotate(FRAC_PI_2) * (self.height as f64 / 2.0);
+        [
+            self.center - along - across,
+            self.center + along - across,
+            self.center + along + across,
+            self.center - along + across,
+        ]
+    }
+}
+
+impl Capsule {
+    // Ends of the line segment in the middle
+    pub fn ends(&self) -> (Vec2, Vec2) {
+        let half = Vec2::from_angle(self.angle) * (self.length as f64 / 2.0);
+        (self.center - half, self.center + half)
+    }
+}
 
 impl Shape {
     pub fn new_point(x: i32, y: i32) -> Self {
@@ -47,6 +88,24 @@ impl Shape {
         })
     }
 
+    pub fn new_oriented_rect(center: Vec2, width: u32, height: u32, angle: f64) -> Self {
+        Shape::OrientedRect(OrientedRect {
+            center,
+            width,
+            height,
+            angle,
+        })
+    }
+
+    pub fn new_capsule(center: Vec2, length: u32, r: u32, angle: f64) -> Self {
+        Shape::Capsule(Capsule {
+            center,
+            length,
+            r,
+            angle,
+        })
+    }
+
     pub fn new_compound() -> Self {
         Shape::Compound(vec![])
     }
@@ -83,6 +142,11 @@ impl Shape {
                 width: 0,
                 height: 0,
             },
+            Shape::OrientedRect(rect) => bounds(&rect.corners(), 0.0),
+            Shape::Capsule(capsule) => {
+                let (a, b) = capsule.ends();
+                bounds(&[a, b], capsule.r as f64)
+            }
             _ => Rectangle {
                 // TODO
                 x: 0,
@@ -93,20 +157,68 @@ impl Shape {
         }
     }
 
-    pub fn advance(&mut self, v: &Velocity) {
+    // center of the bounding box
+    pub fn center(&self) -> (i32, i32) {
+        let bounding_box = self.get_box();
+        (
+            bounding_box.x + (bounding_box.width / 2) as i32,
+            bounding_box.y + (bounding_box.height / 2) as i32,
+        )
+    }
+
+    // The same shape moved by dx, dy
+    pub fn offset(&self, dx: i32, dy: i32) -> Shape {
         match self {
-            Shape::Compound(c) => {
-                for s in c.iter_mut() {
-                    match s {
-                        _ => {}
-                    } //TODO
-                }
+            Shape::Point(p) => Shape::new_point(p.x + dx, p.y + dy),
+            Shape::Circle(c) => Shape::new_circle(c.x + dx, c.y + dy, c.r),
+            Shape::Rectangle(r) => Shape::new_rectangle(r.x + dx, r.y + dy, r.width, r.height),
+            Shape::OrientedRect(r) => Shape::OrientedRect(OrientedRect {
+                center: r.center + Vec2::new(dx as f64, dy as f64),
+                ..*r
+            }),
+            Shape::Capsule(c) => Shape::Capsule(Capsule {
+                center: c.center + Vec2::new(dx as f64, dy as f64),
+                ..*c
+            }),
+            Shape::Compound(shapes) => {
+                Shape::Compound(shapes.iter().map(|x| x.offset(dx, dy)).collect())
             }
-            Shape::Point(Point { x, y })
-            | Shape::Circle(Circle { x, y, .. })
-            | Shape::Rectangle(Rectangle { x, y, .. }) => {
-                *x += v.x.ceil() as i32;
-                *y += v.y.ceil() as i32;
+        }
+    }
+
+    // The same shape turned by angle radians around the pivot. Rectangles
+    // become oriented rectangles.
+    pub fn rotate(&self, angle: f64, pivot: Vec2) -> Shape {
+        let turn = |p: Vec2| pivot + (p - pivot).rotate(angle);
+        let round = |p: Vec2| (p.x.round() as i32, p.y.round() as i32);
+        match self {
+            Shape::Point(p) => {
+                let (x, y) = round(turn(Vec2::new(p.x as f64, p.y as f64)));
+                Shape::new_point(x, y)
+            }
+            Shape::Circle(c) => {
+                let (x, y) = round(turn(Vec2::new(c.x as f64, c.y as f64)));
+                Shape::new_circle(x, y, c.r)
+            }
+            Shape::Rectangle(r) => {
+                let center = Vec2::new(
+                    r.x as f64 + r.width as f64 / 2.0,
+                    r.y as f64 + r.height as f64 / 2.0,
+                );
+                Shape::new_oriented_rect(turn(center), r.width, r.height, angle)
+            }
+            Shape::OrientedRect(r) => Shape::OrientedRect(OrientedRect {
+                center: turn(r.center),
+                angle: r.angle + angle,
+                ..*r
+            }),
+            Shape::Capsule(c) => Shape::Capsule(Capsule {
+                center: turn(c.center),
+                angle: c.angle + angle,
+                ..*c
+            }),
+            Shape::Compound(shapes) => {
+                Shape::Compound(shapes.iter().map(|x| x.rotate(angle, pivot)).collect())
             }
         }
     }
@@ -120,6 +232,22 @@ impl Shape {
     }
 }
 
+// Smallest whole-pixel rectangle around the points, grown by r
+fn bounds(points: &[Vec2], r: f64) -> Rectangle {
+    let (mut min, mut max) = (points[0], points[0]);
+    for p in points {
+        min = Vec2::new(min.x.min(p.x), min.y.min(p.y));
+        max = Vec2::new(max.x.max(p.x), max.y.max(p.y));
+    }
+    let (x, y) = ((min.x - r).floor() as i32, (min.y - r).floor() as i32);
+    Rectangle {
+        x,
+        y,
+        width: ((max.x + r).ceil() as i32 - x) as u32,
+        height: ((max.y + r).ceil() as i32 - y) as u32,
+    }
+}
+
 pub trait Collider {
     fn collide(&self, target: &Shape) -> bool;
     fn is_in_screen(&self) -> bool;
@@ -132,20 +260,33 @@ impl Collider for Shape {
                 Shape::Rectangle(other) => collide_shapes::collide_rect_to_rect(me, other),
                 Shape::Point(other) => collide_shapes::collide_rect_to_point(me, other),
                 Shape::Circle(other) => collide_shapes::collide_rect_to_circle(me, other),
+                Shape::OrientedRect(_) | Shape::Capsule(_) => {
+                    collide_shapes::collide_rounded(self, target)
+                }
                 Shape::Compound(shapes) => shapes.iter().any(|x| x.collide(self)),
             },
             Shape::Circle(me) => match target {
                 Shape::Rectangle(other) => collide_shapes::collide_rect_to_circle(other, me),
                 Shape::Point(other) => collide_shapes::collide_circle_to_point(me, other),
                 Shape::Circle(other) => collide_shapes::collide_circle_to_circle(me, other),
+                Shape::OrientedRect(_) | Shape::Capsule(_) => {
+                    collide_shapes::collide_rounded(self, target)
+                }
                 Shape::Compound(shapes) => shapes.iter().any(|x| x.collide(self)),
             },
             Shape::Point(me) => match target {
                 Shape::Rectangle(other) => collide_shapes::collide_rect_to_point(other, me),
                 Shape::Point(other) => me == other,
                 Shape::Circle(other) => collide_shapes::collide_circle_to_point(other, me),
+                Shape::OrientedRect(_) | Shape::Capsule(_) => {
+                    collide_shapes::collide_rounded(self, target)
+                }
                 Shape::Compound(shapes) => shapes.iter().any(|x| x.collide(self)),
             },
+            Shape::OrientedRect(_) | Shape::Capsule(_) => match target {
+                Shape::Compound(shapes) => shapes.iter().any(|x| x.collide(self)),
+                _ => collide_shapes::collide_rounded(self, target),
+            },
             Shape::Compound(shapes) => shapes.iter().any(|x| x.collide(target)),
         }
     }
@@ -167,34 +308,22 @@ impl Collider for Shape {
                     && circle.y + circle.r as i32 >= 0
                     && circle.y - (circle.r as i32) < HEIGHT as i32
             }
+            Shape::OrientedRect(_) | Shape::Capsule(_) => {
+                let rect = self.get_box();
+                rect.x >= -(rect.width as i32)
+                    && rect.x <= WIDTH as i32
+                    && rect.y >= -(rect.height as i32)
+                    && rect.y <= HEIGHT as i32
+            }
             Shape::Compound(shapes) => shapes.iter().any(|x| x.is_in_screen()),
         }
     }
 }
 
-impl Collider for enemy::Enemy<'_, '_> {
-    fn collide(&self, other: &Shape) -> bool {
-        self.shape.collide(other)
-    }
-
-    fn is_in_screen(&self) -> bool {
-        self.shape.is_in_screen()
-    }
-}
-
-impl Collider for projectile::Projectile<'_, '_> {
-    fn collide(&self, other: &Shape) -> bool {
-        self.shape.collide(other)
-    }
-
-    fn is_in_screen(&self) -> bool {
-        self.shape.is_in_screen()
-    }
-}
-
 mod collide_shapes {
     use super::{Circle, Point, Rectangle};
     use super::{Collider, Shape};
+    use crate::vec2::Vec2;
 
     pub fn collide_rect_to_rect(rect1: &Rectangle, rect2: &Rectangle) -> bool {
         rect1.x < rect2.x + rect2.width as i32
@@ -262,4 +391,247 @@ mod collide_shapes {
             false
         }
     }
+
+    // Any shape other than a compound, as a convex polygon with its corners
+    // rounded off by a radius: a rectangle is four corners and no radius, a
+    // capsule two corners and a radius, and a circle one.
+    struct Rounded {
+        corners: [Vec2; 4],
+        len: usize,
+        radius: f64,
+    }
+
+    impl Rounded {
+        fn new(shape: &Shape) -> Option<Rounded> {
+            let v = |x: i32, y: i32| Vec2::new(x as f64, y as f64);
+            let mut corners = [Vec2::ZERO; 4];
+            let (len, radius) = match shape {
+                Shape::Point(p) => {
+                    corners[0] = v(p.x, p.y);
+                    (1, 0.0)
+                }
+                Shape::Circle(c) => {
+                    corners[0] = v(c.x, c.y);
+                    (1, c.r as f64)
+                }
+                Shape::Rectangle(r) => {
+                    let (right, bottom) = (r.x + r.width as i32, r.y + r.height as i32);
+                    corners = [v(r.x, r.y), v(right, r.y), v(right, bottom), v(r.x, bottom)];
+                    (4, 0.0)
+                }
+                Shape::OrientedRect(r) => {
+                    corners = r.corners();
+                    (4, 0.0)
+                }
+                Shape::Capsule(c) => {
+                    let (a, b) = c.ends();
+                    corners[0] = a;
+                    corners[1] = b;
+                    (2, c.r as f64)
+                }
+                Shape::Compound(_) => return None,
+            };
+            Some(Rounded {
+                corners,
+                len,
+                radius,
+            })
+        }
+
+        fn corners(&self) -> &[Vec2] {
+            &self.corners[..self.len]
+        }
+
+        fn edges(&self) -> impl Iterator<Item = (Vec2, Vec2)> + '_ {
+            let corners = self.corners();
+            let edges = if corners.len() > 1 { corners.len() } else { 0 };
+            (0..edges).map(move |i| (corners[i], corners[(i + 1) % corners.len()]))
+        }
+
+        // Directions to project on when looking for a gap: the normal of
+        // every edge, and for a line segment its direction too. Edges of no
+        // length, like a capsule's that's as short as it is wide, have none.
+        fn axes(&self) -> impl Iterator<Item = Vec2> + '_ {
+            let along = if self.len == 2 {
+                Some(self.corners[1] - self.corners[0])
+            } else {
+                None
+            };
+            self.edges()
+                .map(|(a, b)| Vec2::new(a.y - b.y, b.x - a.x))
+                .chain(along)
+                .filter(|&x| x != Vec2::ZERO)
+        }
+
+        fn project(&self, axis: Vec2) -> (f64, f64) {
+            self.corners()
+                .iter()
+                .map(|x| x.dot(axis))
+                .fold((f64::INFINITY, f64::NEG_INFINITY), |(min, max), x| {
+                    (min.min(x), max.max(x))
+                })
+        }
+    }
+
+    // Separating axis test: two convex polygons overlap unless they can be
+    // told apart by projecting them on one of their edge normals. Like
+    // rectangles, polygons that only touch don't overlap.
+    fn polygons_overlap(a: &Rounded, b: &Rounded) -> bool {
+        if a.len == 1 && b.len == 1 {
+            return a.corners[0] == b.corners[0];
+        }
+        a.axes().chain(b.axes()).all(|axis| {
+            let (a_min, a_max) = a.project(axis);
+            let (b_min, b_max) = b.project(axis);
+            a_min < b_max && b_min < a_max
+        })
+    }
+
+    fn point_to_segment(p: Vec2, a: Vec2, b: Vec2) -> f64 {
+        let ab = b - a;
+        let length_squared = ab.dot(ab);
+        if length_squared == 0.0 {
+            return (p - a).length();
+        }
+        let t = ((p - a).dot(ab) / length_squared).clamp(0.0, 1.0);
+        (p - (a + ab * t)).length()
+    }
+
+    // Shortest distance from a polygon's corners to the other's outline
+    fn corners_to_outline(a: &Rounded, b: &Rounded) -> f64 {
+        let mut distance = f64::INFINITY;
+        for &p in a.corners() {
+            if b.len == 1 {
+                distance = distance.min((p - b.corners[0]).length());
+            }
+            for (start, end) in b.edges() {
+                distance = distance.min(point_to_segment(p, start, end));
+            }
+        }
+        distance
+    }
+
+    // Rounded shapes collide when their polygons overlap, or come closer than
+    // the radii put together
+    pub fn collide_rounded(a: &Shape, b: &Shape) -> bool {
+        let (a, b) = match (Rounded::new(a), Rounded::new(b)) {
+            (Some(a), Some(b)) => (a, b),
+            _ => return false,
+        };
+        if polygons_overlap(&a, &b) {
+            return true;
+        }
+        let distance = corners_to_outline(&a, &b).min(corners_to_outline(&b, &a));
+        distance < a.radius + b.radius
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::f64::consts::FRAC_PI_4;
+
+    // both ways round, since either shape can be the one asking
+    fn collide(a: &Shape, b: &Shape) -> bool {
+        let result = a.collide(b);
+        assert_eq!(result, b.collide(a), "{:?} and {:?} disagree", a, b);
+        result
+    }
+
+    // 20 by 10 turned on its side: x from 45 to 55, y from 40 to 60
+    fn upright() -> Shape {
+        Shape::new_oriented_rect(Vec2::new(50.0, 50.0), 20, 10, FRAC_PI_2)
+    }
+
+    #[test]
+    fn oriented_rect_and_rect() {
+        assert!(collide(&upright(), &Shape::new_rectangle(50, 50, 10, 10)));
+        assert!(collide(&upright(), &Shape::new_rectangle(54, 45, 10, 10)));
+        assert!(!collide(&upright(), &Shape::new_rectangle(56, 45, 10, 10)));
+    }
+
+    #[test]
+    fn oriented_rect_and_circle() {
+        assert!(collide(&upright(), &Shape::new_circle(50, 50, 3)));
+        assert!(collide(&upright(), &Shape::new_circle(59, 50, 5)));
+        assert!(!collide(&upright(), &Shape::new_circle(61, 50, 5)));
+    }
+
+    #[test]
+    fn oriented_rect_and_point() {
+        assert!(collide(&upright(), &Shape::new_point(52, 58)));
+        assert!(collide(&upright(), &Shape::new_point(54, 59)));
+        assert!(!collide(&upright(), &Shape::new_point(56, 50)));
+    }
+
+    #[test]
+    fn oriented_rect_and_capsule() {
+        // a flat capsule 10 long with round ends 5 across, reaching 15 left
+        // of its centre
+        let capsule = |x: f64| Shape::new_capsule(Vec2::new(x, 50.0), 10, 5, 0.0);
+        assert!(collide(&upright(), &capsule(62.0)));
+        assert!(collide(&upright(), &capsule(64.0)));
+        assert!(!collide(&upright(), &capsule(66.0)));
+    }
+
+    #[test]
+    fn turned_corners_miss() {
+        // a 10 by 10 square turned into a diamond around the origin, whose
+        // bounding box overlaps both rectangles but whose sides only reach
+        // the first
+        let diamond = Shape::new_oriented_rect(Vec2::ZERO, 10, 10, FRAC_PI_4);
+        assert!(collide(&diamond, &Shape::new_rectangle(6, -1, 4, 2)));
+        assert!(!collide(&diamond, &Shape::new_rectangle(5, 3, 4, 2)));
+    }
+
+    #[test]
+    fn capsule_and_shapes() {
+        // from (40, 50) to (60, 50), 4 thick on either side
+        let laser = Shape::new_capsule(Vec2::new(50.0, 50.0), 20, 4, 0.0);
+        assert!(collide(&laser, &Shape::new_point(62, 51)));
+        assert!(collide(&laser, &Shape::new_point(50, 53)));
+        assert!(!collide(&laser, &Shape::new_point(50, 54)));
+        assert!(collide(&laser, &Shape::new_circle(50, 59, 6)));
+        assert!(!collide(&laser, &Shape::new_circle(50, 60, 6)));
+        assert!(collide(&laser, &Shape::new_rectangle(30, 53, 40, 4)));
+        assert!(!collide(&laser, &Shape::new_rectangle(30, 54, 40, 4)));
+    }
+
+    #[test]
+    fn turned_capsule() {
+        // standing upright, from (50, 40) to (50, 60)
+        let laser = Shape::new_capsule(Vec2::new(50.0, 50.0), 20, 4, FRAC_PI_2);
+        assert!(collide(&laser, &Shape::new_point(51, 62)));
+        assert!(collide(&laser, &Shape::new_point(53, 50)));
+        assert!(!collide(&laser, &Shape::new_point(55, 50)));
+    }
+
+    #[test]
+    fn touching_is_not_colliding() {
+        // as for two plain rectangles side by side
+        let square = Shape::new_rectangle(0, 0, 10, 10);
+        assert!(!collide(&square, &Shape::new_rectangle(10, 0, 10, 10)));
+        let turned = |x: f64, y: f64| Shape::new_oriented_rect(Vec2::new(x, y), 10, 10, 0.0);
+        assert!(!collide(&square, &turned(15.0, 5.0)));
+        assert!(!collide(&turned(5.0, 5.0), &turned(15.0, 5.0)));
+        assert!(collide(&square, &turned(14.0, 5.0)));
+        // corner to corner
+        assert!(!collide(&square, &turned(15.0, 15.0)));
+        // a capsule whose round end just reaches the side
+        let capsule = |x: f64| Shape::new_capsule(Vec2::new(x, 5.0), 10, 5, 0.0);
+        assert!(!collide(&square, &capsule(20.0)));
+        assert!(collide(&square, &capsule(19.0)));
+        // and one lying along the side
+        let along = Shape::new_capsule(Vec2::new(5.0, 15.0), 10, 5, 0.0);
+        assert!(!collide(&square, &along));
+    }
+
+    #[test]
+    fn capsule_as_short_as_it_is_wide() {
+        // just a circle, whose middle is well inside the rectangle
+        let round = Shape::new_capsule(Vec2::new(50.0, 50.0), 0, 5, 0.0);
+        assert!(collide(&round, &Shape::new_rectangle(0, 0, 100, 100)));
+        assert!(collide(&round, &Shape::new_rectangle(54, 0, 100, 100)));
+        assert!(!collide(&round, &Shape::new_rectangle(55, 0, 100, 100)));
+    }
 }
@@ -0,0 +1,84 @@
+use crate::animation::{AnimationPlayer, SpriteSheet};
+use crate::layers::Layer;
+use crate::projectile::ProjShape;
+use sdl2::pixels::Color;
+use std::time::Duration;
+
+#[derive(Clone, Copy)]
+pub struct Sprite<'a, 'b> {
+    pub sheet: &'a SpriteSheet<'b>,
+    pub anim: AnimationPlayer,
+    // colours are multiplied by this, if set
+    pub tint: Option<Color>,
+}
+
+impl<'a, 'b> Sprite<'a, 'b> {
+    pub fn new(sheet: &'a SpriteSheet<'b>, animation: &str) -> Self {
+        Sprite {
+            sheet,
+            anim: AnimationPlayer::new(&sheet.atlas(), animation),
+            tint: None,
+        }
+    }
+
+    // Size of the frame currently showing
+    pub fn size(&self) -> (u32, u32) {
+        let frame = self.anim.frame(&self.sheet.atlas());
+        (frame.width(), frame.height())
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct Health {
+    pub hp: u32,
+    // game clock time until which hits are ignored
+    pub invulnerable_until: Duration,
+    // never takes damage, for the developer console
+    pub invincible: bool,
+}
+
+impl Health {
+    pub fn new(hp: u32) -> Self {
+        Health {
+            hp,
+            invulnerable_until: Duration::from_secs(0),
+            invincible: false,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Aim {
+    // straight ahead, in the direction of the projectile speed
+    Forward,
+    // at the player's ship
+    AtPlayer,
+    // where the player's ship will be when the shot gets there, if it keeps
+    // flying the way it does
+    Leading,
+    // at the player's ship, off by up to this many radians either way
+    Spread(f64),
+    // this many radians clockwise from straight ahead, wherever the player is
+    Angle(f64),
+}
+
+#[derive(Clone, Copy)]
+pub struct Weapon<'a, 'b> {
+    pub sprite: &'a SpriteSheet<'b>,
+    // negative to fire to the left
+    pub speed: f64,
+    pub cooldown: Duration,
+    pub last_shot: Option<Duration>, // game clock time
+    // number of projectiles fired at once, fanned out
+    pub shots: u32,
+    pub aim: Aim,
+    pub shape: ProjShape,
+    // collision layer of the projectiles
+    pub layer: Layer,
+    // fire whenever the cooldown allows
+    pub trigger: bool,
+}
+
+// Stopped at the screen edges instead of flying off
+#[derive(Debug, Clone, Copy)]
+pub struct KeepOnScreen;
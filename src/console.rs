@@ -0,0 +1,175 @@
+use crate::text;
+use crate::timing::{MAX_TIME_SCALE, MIN_TIME_SCALE};
+use sdl2::event::Event;
+use sdl2::keyboard::Keycode;
+use sdl2::pixels::Color;
+use sdl2::rect::Rect;
+use sdl2::render::{BlendMode, Canvas};
+use sdl2::video::Window;
+
+// Lines of output kept and shown
+const MAX_LINES: usize = 12;
+const LINE_HEIGHT: i32 = text::GLYPH_HEIGHT as i32 + 3;
+
+pub const HELP: &[&str] = &[
+    "spawn enemy <type> <x> <y>",
+    "god",
+    "give weapon <n>",
+    "timescale <scale>",
+    "seed [n]",
+    "level <name>",
+];
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Command {
+    SpawnEnemy { kind: String, x: i32, y: i32 },
+    // toggle invulnerability
+    God,
+    GiveWeapon(u32),
+    TimeScale(f64),
+    // show the seed, or restart with a new one
+    Seed(Option<u64>),
+    Level(String),
+    Help,
+}
+
+impl Command {
+    pub fn parse(line: &str) -> Result<Command, String> {
+        let words: Vec<&str> = line.split_whitespace().collect();
+        let num = |s: &str| s.parse::<i32>().map_err(|_| format!("not a number: {}", s));
+        match words.as_slice() {
+            ["spawn", "enemy", kind, x, y] => Ok(Command::SpawnEnemy {
+                kind: kind.to_string(),
+                x: num(x)?,
+                y: num(y)?,
+            }),
+            ["god"] => Ok(Command::God),
+            ["give", "weapon", n] => n
+                .parse()
+                .map(Command::GiveWeapon)
+                .map_err(|_| format!("not a weapon number: {}", n)),
+            ["timescale", scale] => match scale.parse::<f64>() {
+                Ok(x) if (MIN_TIME_SCALE..=MAX_TIME_SCALE).contains(&x) => {
+                    Ok(Command::TimeScale(x))
+                }
+                _ => Err(format!(
+                    "time scale must be between {} and {}",
+                    MIN_TIME_SCALE, MAX_TIME_SCALE
+                )),
+            },
+            ["seed"] => Ok(Command::Seed(None)),
+            ["seed", n] => n
+                .parse()
+                .map(|x| Command::Seed(Some(x)))
+                .map_err(|_| format!("not a seed: {}", n)),
+            ["level", name] => Ok(Command::Level(name.to_string())),
+            ["help"] => Ok(Command::Help),
+            [] => Err("".to_string()),
+            _ => Err(format!("unknown command: {}. try help", line.trim())),
+        }
+    }
+}
+
+// Drop-down developer console, opened with the backquote key.
+// While it's open it takes all keyboard input.
+pub struct Console {
+    open: bool,
+    input: String,
+    lines: Vec<String>,
+    // previously entered commands, for recalling with the up arrow
+    entered: Vec<String>,
+    recall: usize,
+}
+
+impl Default for Console {
+    fn default() -> Self {
+        Console::new()
+    }
+}
+
+impl Console {
+    pub fn new() -> Self {
+        Console {
+            open: false,
+            input: String::new(),
+            lines: vec![],
+            entered: vec![],
+            recall: 0,
+        }
+    }
+
+    pub fn is_open(&self) -> bool {
+        self.open
+    }
+
+    pub fn toggle(&mut self) {
+        self.open = !self.open;
+        self.input.clear();
+    }
+
+    pub fn print(&mut self, line: &str) {
+        self.lines.push(line.to_string());
+        if self.lines.len() > MAX_LINES {
+            self.lines.remove(0);
+        }
+    }
+
+    // Handle a key or text event while the console is open.
+    // Returns the command line when one is entered.
+    pub fn handle_event(&mut self, event: &Event) -> Option<String> {
+        match event {
+            // the backquote that opens the console also arrives as text
+            Event::TextInput { text, .. } => {
+                self.input.extend(text.chars().filter(|&x| x != '`'));
+            }
+            Event::KeyDown {
+                keycode: Some(key), ..
+            } => match key {
+                Keycode::Backspace => {
+                    self.input.pop();
+                }
+                Keycode::Escape => self.toggle(),
+                Keycode::Up if self.recall > 0 => {
+                    self.recall -= 1;
+                    self.input = self.entered[self.recall].clone();
+                }
+                Keycode::Down if self.recall < self.entered.len() => {
+                    self.recall += 1;
+                    self.input = self.entered.get(self.recall).cloned().unwrap_or_default();
+                }
+                Keycode::Return | Keycode::KpEnter => {
+                    let line = self.input.trim().to_string();
+                    self.input.clear();
+                    if line.is_empty() {
+                        return None;
+                    }
+                    self.print(&format!("> {}", line));
+                    self.entered.push(line.clone());
+                    self.recall = self.entered.len();
+                    return Some(line);
+                }
+                _ => {}
+            },
+            _ => {}
+        }
+        None
+    }
+
+    pub fn draw(&self, canvas: &mut Canvas<Window>) -> Result<(), String> {
+        let height = (MAX_LINES as i32 + 1) * LINE_HEIGHT + 6;
+        canvas.set_blend_mode(BlendMode::Blend);
+        canvas.set_draw_color(Color::RGBA(0x10, 0x10, 0x20, 0xd0));
+        canvas.fill_rect(Rect::new(0, 0, crate::WIDTH, height as u32))?;
+        canvas.set_blend_mode(BlendMode::None);
+
+        let color = Color::RGB(0xc0, 0xc0, 0xc0);
+        let first_line = MAX_LINES - self.lines.len();
+        for (i, line) in self.lines.iter().enumerate() {
+            let y = 4 + (first_line + i) as i32 * LINE_HEIGHT;
+            text::draw_text(canvas, line, 4, y, 1, color)?;
+        }
+        let prompt = format!("] {}_", self.input);
+        let y = 4 + MAX_LINES as i32 * LINE_HEIGHT;
+        text::draw_text(canvas, &prompt, 4, y, 1, Color::RGB(0xff, 0xff, 0xff))
+    }
+}
@@ -0,0 +1,191 @@
+use crate::collide::Shape;
+use crate::text;
+use crate::timing::FrameStats;
+use crate::vec2::Vec2;
+use sdl2::pixels::Color;
+use sdl2::rect::{Point, Rect};
+use sdl2::render::{BlendMode, Canvas};
+use sdl2::video::Window;
+use std::f64::consts::PI;
+use std::time::Duration;
+
+// Velocity lines are this many frames of movement long, so slow things show up too
+const VELOCITY_SCALE: f64 = 4.0;
+const CIRCLE_SEGMENTS: usize = 24;
+
+// What the debug overlay shows besides frame timing
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Counts {
+    pub enemies: usize,
+    pub projectiles: usize,
+    pub particles: usize,
+    pub collision_checks: usize,
+    // heap allocations so far this frame
+    pub allocations: usize,
+    // dynamic difficulty, if it's on
+    pub rank: Option<f64>,
+    // frames played again after the last rollback, in a network game
+    pub rollback: Option<u32>,
+}
+
+fn ms(d: Duration) -> String {
+    format!("{:.1}", d.as_secs_f64() * 1000.0)
+}
+
+// Frame timing and entity counts in the bottom left corner
+pub fn draw_overlay(
+    canvas: &mut Canvas<Window>,
+    stats: &FrameStats,
+    vsync: bool,
+    counts: &Counts,
+) -> Result<(), String> {
+    let lines = [
+        format!(
+            "FPS {:.0} {}",
+            stats.fps,
+            if vsync { "VSYNC" } else { "SLEEP" }
+        ),
+        format!("FRAME MS AVG {}", ms(stats.average)),
+        format!(
+            "P50 {} P95 {} P99 {} MAX {}",
+            ms(stats.p50),
+            ms(stats.p95),
+            ms(stats.p99),
+            ms(stats.max)
+        ),
+        format!(
+            "ENEMIES {} SHOTS {} PARTICLES {}",
+            counts.enemies, counts.projectiles, counts.particles
+        ),
+        format!("COLLISION CHECKS {}", counts.collision_checks),
+        format!("ALLOCATIONS {}", counts.allocations),
+        match counts.rank {
+            Some(rank) => format!("RANK {:.2}", rank),
+            None => "RANK OFF".to_string(),
+        },
+        match counts.rollback {
+            Some(frames) => format!("ROLLBACK {}", frames),
+            None => "NETPLAY OFF".to_string(),
+        },
+    ];
+    let line_height = text::GLYPH_HEIGHT as i32 + 3;
+    let width = lines
+        .iter()
+        .map(|x| text::text_width(x, 1))
+        .max()
+        .unwrap_or(0)
+        + 8;
+    let height = lines.len() as i32 * line_height + 5;
+    let top = crate::HEIGHT as i32 - height;
+
+    canvas.set_blend_mode(BlendMode::Blend);
+    canvas.set_draw_color(Color::RGBA(0, 0, 0, 0xa0));
+    canvas.fill_rect(Rect::new(0, top, width, height as u32))?;
+    canvas.set_blend_mode(BlendMode::None);
+    let color = Color::RGB(0x80, 0xff, 0x80);
+    for (i, line) in lines.iter().enumerate() {
+        text::draw_text(canvas, line, 4, top + 4 + i as i32 * line_height, 1, color)?;
+    }
+    Ok(())
+}
+
+// Shown in the bottom right corner while the game runs at an unusual speed
+pub fn draw_time_status(
+    canvas: &mut Canvas<Window>,
+    frozen: bool,
+    time_scale: f64,
+) -> Result<(), String> {
+    let status = if frozen {
+        "FROZEN - F6 STEP".to_string()
+    } else if (time_scale - 1.0).abs() > f64::EPSILON {
+        format!("TIME {}X", time_scale)
+    } else {
+        return Ok(());
+    };
+    let x = crate::WIDTH as i32 - 4 - text::text_width(&status, 1) as i32;
+    let y = crate::HEIGHT as i32 - 4 - text::GLYPH_HEIGHT as i32;
+    text::draw_text(canvas, &status, x, y, 1, Color::RGB(0xff, 0xc0, 0x40))
+}
+
+// Outline a collision shape. Compound shapes draw each of their parts.
+pub fn draw_shape(canvas: &mut Canvas<Window>, shape: &Shape, color: Color) -> Result<(), String> {
+    canvas.set_draw_color(color);
+    match shape {
+        Shape::Point(p) => canvas.draw_point(Point::new(p.x, p.y)),
+        Shape::Rectangle(rect) => canvas.draw_rect(Rect::new(
+            rect.x,
+            rect.y,
+            rect.width.max(1),
+            rect.height.max(1),
+        )),
+        Shape::Circle(c) => draw_arc(
+            canvas,
+            Vec2::new(c.x as f64, c.y as f64),
+            c.r as f64,
+            0.0,
+            PI * 2.0,
+        ),
+        Shape::OrientedRect(rect) => {
+            let corners = rect.corners();
+            let points: Vec<Point> = corners
+                .iter()
+                .chain(&corners[..1])
+                .map(|p| Point::new(p.x.round() as i32, p.y.round() as i32))
+                .collect();
+            canvas.draw_lines(&points[..])
+        }
+        Shape::Capsule(capsule) => {
+            // two half circles joined by the sides
+            let (a, b) = capsule.ends();
+            let r = capsule.r as f64;
+            let side = Vec2::from_angle(capsule.angle + PI / 2.0) * r;
+            let point = |p: Vec2| Point::new(p.x.round() as i32, p.y.round() as i32);
+            canvas.draw_line(point(a + side), point(b + side))?;
+            canvas.draw_line(point(a - side), point(b - side))?;
+            draw_arc(canvas, a, r, capsule.angle + PI / 2.0, PI)?;
+            draw_arc(canvas, b, r, capsule.angle - PI / 2.0, PI)
+        }
+        Shape::Compound(shapes) => {
+            for part in shapes {
+                draw_shape(canvas, part, color)?;
+            }
+            Ok(())
+        }
+    }
+}
+
+// Part of a circle, from the start angle clockwise by length radians
+fn draw_arc(
+    canvas: &mut Canvas<Window>,
+    center: Vec2,
+    r: f64,
+    start: f64,
+    length: f64,
+) -> Result<(), String> {
+    let segments = ((CIRCLE_SEGMENTS as f64 * length / (PI * 2.0)).ceil() as usize).max(1);
+    let points: Vec<Point> = (0..=segments)
+        .map(|i| {
+            let p = center + Vec2::from_angle(start + length * i as f64 / segments as f64) * r;
+            Point::new(p.x.round() as i32, p.y.round() as i32)
+        })
+        .collect();
+    canvas.draw_lines(&points[..])
+}
+
+// Line from the centre of a shape in the direction it's moving
+pub fn draw_velocity(
+    canvas: &mut Canvas<Window>,
+    shape: &Shape,
+    v: &Vec2,
+    color: Color,
+) -> Result<(), String> {
+    let (x, y) = shape.center();
+    canvas.set_draw_color(color);
+    canvas.draw_line(
+        Point::new(x, y),
+        Point::new(
+            x + (v.x * VELOCITY_SCALE) as i32,
+            y + (v.y * VELOCITY_SCALE) as i32,
+        ),
+    )
+}
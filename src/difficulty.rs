@@ -0,0 +1,105 @@
+// How hard the game is: a preset picked in the options menu, and optionally
+// a rank that climbs while the player does well and drops when they die.
+
+// Rank gained for every enemy shot down
+const KILL_RANK: f64 = 0.02;
+// Rank gained for every frame without dying, a full rank in about 3 minutes
+const SURVIVAL_RANK: f64 = 1.0 / (50.0 * 180.0);
+// Extra rank for every weapon level past the first
+const WEAPON_RANK: f64 = 0.1;
+// Fraction of rank kept after dying
+const DEATH_RANK: f64 = 0.5;
+// At full rank enemies spawn, move and shoot this much more than the preset
+const MAX_RANK_EFFECT: f64 = 0.6;
+
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum Difficulty {
+    Easy,
+    Normal,
+    Hard,
+}
+
+impl Difficulty {
+    pub const ALL: [Difficulty; 3] = [Difficulty::Easy, Difficulty::Normal, Difficulty::Hard];
+
+    // The next or previous preset, wrapping around
+    pub fn cycle(self, delta: i32) -> Difficulty {
+        let count = Difficulty::ALL.len() as i32;
+        let i = Difficulty::ALL.iter().position(|&x| x == self).unwrap() as i32;
+        Difficulty::ALL[(i + delta).rem_euclid(count) as usize]
+    }
+
+    pub fn label(self) -> &'static str {
+        match self {
+            Difficulty::Easy => "EASY",
+            Difficulty::Normal => "NORMAL",
+            Difficulty::Hard => "HARD",
+        }
+    }
+
+    fn modifiers(self) -> Modifiers {
+        match self {
+            Difficulty::Easy => Modifiers {
+                spawn_chance: 0.6,
+                speed: 0.8,
+                fire_rate: 0.6,
+            },
+            Difficulty::Normal => Modifiers {
+                spawn_chance: 1.0,
+                speed: 1.0,
+                fire_rate: 1.0,
+            },
+            Difficulty::Hard => Modifiers {
+                spawn_chance: 1.5,
+                speed: 1.25,
+                fire_rate: 1.6,
+            },
+        }
+    }
+}
+
+// Multipliers for how often enemies appear, how fast they fly and how often
+// they shoot, compared to normal difficulty
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Modifiers {
+    pub spawn_chance: f64,
+    pub speed: f64,
+    pub fire_rate: f64,
+}
+
+// Dynamic difficulty, between 0.0 and 1.0
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct Rank {
+    value: f64,
+}
+
+impl Rank {
+    pub fn kill(&mut self) {
+        self.value = (self.value + KILL_RANK).min(1.0);
+    }
+
+    // Called every frame the player stays alive
+    pub fn survive(&mut self) {
+        self.value = (self.value + SURVIVAL_RANK).min(1.0);
+    }
+
+    pub fn die(&mut self) {
+        self.value *= DEATH_RANK;
+    }
+
+    // Rank including the player's weapon level
+    pub fn value(&self, weapon_level: u32) -> f64 {
+        (self.value + weapon_level.saturating_sub(1) as f64 * WEAPON_RANK).min(1.0)
+    }
+}
+
+// The modifiers for a preset, pushed up by the rank if there is one
+pub fn modifiers(difficulty: Difficulty, rank: Option<f64>) -> Modifiers {
+    let preset = difficulty.modifiers();
+    let boost = 1.0 + rank.unwrap_or(0.0) * MAX_RANK_EFFECT;
+    Modifiers {
+        spawn_chance: preset.spawn_chance * boost,
+        speed: preset.speed * boost,
+        fire_rate: preset.fire_rate * boost,
+    }
+}
@@ -0,0 +1,189 @@
+use crate::{HEIGHT, WIDTH};
+use sdl2::pixels::PixelFormatEnum;
+use sdl2::rect::Rect;
+use sdl2::render::{BlendMode, Canvas, Texture, TextureCreator};
+use sdl2::video::{FullscreenType, Window, WindowContext};
+
+pub const MAX_SCALE: u32 = 6;
+const SCANLINE_ALPHA: u8 = 0x60;
+
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum WindowMode {
+    Windowed,
+    // fullscreen at the desktop resolution, without changing video mode
+    Borderless,
+    Fullscreen,
+}
+
+impl WindowMode {
+    pub const ALL: [WindowMode; 3] = [
+        WindowMode::Windowed,
+        WindowMode::Borderless,
+        WindowMode::Fullscreen,
+    ];
+
+    // The next or previous mode, wrapping around
+    pub fn cycle(self, delta: i32) -> WindowMode {
+        let count = WindowMode::ALL.len() as i32;
+        let i = WindowMode::ALL.iter().position(|&x| x == self).unwrap() as i32;
+        WindowMode::ALL[(i + delta).rem_euclid(count) as usize]
+    }
+
+    pub fn label(self) -> &'static str {
+        match self {
+            WindowMode::Windowed => "WINDOWED",
+            WindowMode::Borderless => "BORDERLESS",
+            WindowMode::Fullscreen => "FULLSCREEN",
+        }
+    }
+}
+
+// Window mode, scaling and the optional scanline filter.
+//
+// The game always draws in its WIDTH x HEIGHT logical space. SDL scales that
+// up by the largest whole number that fits the window and letterboxes the rest,
+// so pixels stay square and sharp however the window is sized.
+pub struct Display<'b> {
+    texture_creator: &'b TextureCreator<WindowContext>,
+    mode: WindowMode,
+    scale: u32,
+    scanlines: bool,
+    // scanline overlay and the scale it was made for
+    overlay: Option<(u32, Texture<'b>)>,
+}
+
+impl<'b> Display<'b> {
+    pub fn new(
+        texture_creator: &'b TextureCreator<WindowContext>,
+        canvas: &mut Canvas<Window>,
+        scale: u32,
+    ) -> Result<Self, String> {
+        canvas
+            .set_logical_size(WIDTH, HEIGHT)
+            .map_err(|e| e.to_string())?;
+        // not wrapped by the sdl2 crate
+        unsafe {
+            sdl2::sys::SDL_RenderSetIntegerScale(canvas.raw(), sdl2::sys::SDL_bool::SDL_TRUE);
+        }
+        canvas
+            .window_mut()
+            .set_minimum_size(WIDTH, HEIGHT)
+            .map_err(|e| e.to_string())?;
+        let mut display = Display {
+            texture_creator,
+            mode: WindowMode::Windowed,
+            scale: 1,
+            scanlines: false,
+            overlay: None,
+        };
+        display.set_scale(canvas, scale)?;
+        Ok(display)
+    }
+
+    pub fn mode(&self) -> WindowMode {
+        self.mode
+    }
+
+    pub fn scale(&self) -> u32 {
+        self.scale
+    }
+
+    pub fn scanlines(&self) -> bool {
+        self.scanlines
+    }
+
+    pub fn set_mode(
+        &mut self,
+        canvas: &mut Canvas<Window>,
+        mode: WindowMode,
+    ) -> Result<(), String> {
+        canvas.window_mut().set_fullscreen(match mode {
+            WindowMode::Windowed => FullscreenType::Off,
+            WindowMode::Borderless => FullscreenType::Desktop,
+            WindowMode::Fullscreen => FullscreenType::True,
+        })?;
+        self.mode = mode;
+        Ok(())
+    }
+
+    // Switch between windowed and borderless fullscreen
+    pub fn toggle_fullscreen(&mut self, canvas: &mut Canvas<Window>) -> Result<(), String> {
+        let mode = if self.mode == WindowMode::Windowed {
+            WindowMode::Borderless
+        } else {
+            WindowMode::Windowed
+        };
+        self.set_mode(canvas, mode)
+    }
+
+    // Resize the window to a multiple of the game resolution
+    pub fn set_scale(&mut self, canvas: &mut Canvas<Window>, scale: u32) -> Result<(), String> {
+        self.scale = scale.clamp(1, MAX_SCALE);
+        let window = canvas.window_mut();
+        window
+            .set_size(WIDTH * self.scale, HEIGHT * self.scale)
+            .map_err(|e| e.to_string())?;
+        window.set_position(
+            sdl2::video::WindowPos::Centered,
+            sdl2::video::WindowPos::Centered,
+        );
+        Ok(())
+    }
+
+    pub fn set_scanlines(&mut self, scanlines: bool) {
+        self.scanlines = scanlines;
+    }
+
+    // Whole number the logical screen is currently scaled up by
+    fn output_scale(canvas: &Canvas<Window>) -> Result<u32, String> {
+        let (w, h) = canvas.output_size()?;
+        Ok((w / WIDTH).min(h / HEIGHT).max(1))
+    }
+
+    // Darken the bottom row of real pixels of every game pixel row.
+    // Draw this last, on top of everything else.
+    pub fn draw_scanlines(&mut self, canvas: &mut Canvas<Window>) -> Result<(), String> {
+        if !self.scanlines {
+            return Ok(());
+        }
+        let scale = Display::output_scale(canvas)?;
+        if scale < 2 {
+            return Ok(());
+        }
+        let stale = match &self.overlay {
+            Some((overlay_scale, _)) => *overlay_scale != scale,
+            None => true,
+        };
+        if stale {
+            self.overlay = Some((scale, self.create_overlay(scale)?));
+        }
+        if let Some((_, overlay)) = &self.overlay {
+            // the overlay is one pixel wide and as tall as the screen in real
+            // pixels, so stretching it over the logical screen maps its rows
+            // exactly onto the real ones
+            canvas.copy(overlay, None, Rect::new(0, 0, WIDTH, HEIGHT))?;
+        }
+        Ok(())
+    }
+
+    fn create_overlay(&self, scale: u32) -> Result<Texture<'b>, String> {
+        let mut overlay = self
+            .texture_creator
+            .create_texture_streaming(PixelFormatEnum::RGBA8888, 1, HEIGHT * scale)
+            .map_err(|e| e.to_string())?;
+        overlay.set_blend_mode(BlendMode::Blend);
+        overlay.with_lock(None, |buffer: &mut [u8], pitch: usize| {
+            for row in 0..(HEIGHT * scale) as usize {
+                // black, with alpha in the lowest byte of the packed pixel
+                let alpha = if row as u32 % scale == scale - 1 {
+                    SCANLINE_ALPHA
+                } else {
+                    0
+                };
+                let pixel = u32::from(alpha).to_ne_bytes();
+                buffer[row * pitch..row * pitch + 4].copy_from_slice(&pixel);
+            }
+        })?;
+        Ok(overlay)
+    }
+}
@@ -0,0 +1,10 @@
+// Asset files compiled into the binary, generated by build.rs
+include!(concat!(env!("OUT_DIR"), "/embedded_assets.rs"));
+
+pub fn get(name: &str) -> Option<&'static [u8]> {
+    let name = name.replace('\\', "/");
+    FILES
+        .iter()
+        .find(|(x, _)| *x == name)
+        .map(|(_, data)| *data)
+}
@@ -1,69 +1,127 @@
 use crate::collide::Shape;
-use crate::Projectile;
-use crate::{Axis, Velocity};
-use crate::{HEIGHT, WIDTH};
+use crate::components::{Aim, Health, Sprite, Weapon};
+use crate::difficulty::Modifiers;
+use crate::game::Sprites;
+use crate::layers::Layer;
+use crate::projectile::ProjShape;
+use crate::vec2::Vec2;
+use crate::world::{Entity, World};
+use crate::{ENEMY_PROJECTILE_SPEED, HEIGHT, WIDTH};
 use rand::prelude::*;
-use sdl2::rect::Rect;
-use sdl2::render::{Canvas, Texture};
-use sdl2::video::Window;
-use std::time::{Duration, Instant};
+use std::f64::consts::PI;
+use std::time::Duration;
 
-pub struct Enemy<'a, 'b> {
-    sprite: &'a Texture<'b>,
-    v: Velocity,
-    alive: bool,
-    pub shape: Shape,
-    pub shoot_freq: u64, // milliseconds
-    pub last_shot: Option<Instant>,
+const SHOOT_FREQ: Duration = Duration::from_millis(2000);
+const GUNNER_SPREAD: f64 = 0.35;
+// down and to the left
+const BOMBER_ANGLE: f64 = -PI / 4.0;
+
+// What kind of enemy it is decides how it aims
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum Kind {
+    // shoots straight at the player
+    Fighter,
+    // shoots where the player is going
+    Ace,
+    // shoots roughly at the player
+    Gunner,
+    // drops shots at an angle, wherever the player is
+    Bomber,
 }
 
-impl<'a, 'b> Enemy<'a, 'b> {
-    pub fn from_sprite(sprite: &'a Texture<'b>) -> Self {
-        const MAX_SPEED: f64 = 4.0;
-        const MIN_SPEED: f64 = 1.0;
-        let mut rng = thread_rng();
-        let h = sprite.query().height;
-        let w = sprite.query().width;
+impl Kind {
+    pub const ALL: [Kind; 4] = [Kind::Fighter, Kind::Ace, Kind::Gunner, Kind::Bomber];
 
-        Enemy {
-            sprite,
-            v: Velocity::new(-rng.gen_range(MIN_SPEED, MAX_SPEED + 1.0), 0.0),
-            alive: true,
-            shape: Shape::new_rectangle(
-                (WIDTH - w) as i32,
-                rng.gen_range(1, HEIGHT - h) as i32,
-                h,
-                w,
-            ),
-            shoot_freq: 2000,
-            last_shot: None,
+    pub fn name(self) -> &'static str {
+        match self {
+            Kind::Fighter => "fighter",
+            Kind::Ace => "ace",
+            Kind::Gunner => "gunner",
+            Kind::Bomber => "bomber",
         }
     }
 
-    pub fn draw(&self, canvas: &mut Canvas<Window>) -> Result<(), String> {
-        let bounding_box = self.shape.get_box();
-        let rect = Rect::new(
-            bounding_box.x,
-            bounding_box.y,
-            bounding_box.width,
-            bounding_box.height,
-        );
-        canvas.copy(self.sprite, None, rect)
+    pub fn from_name(name: &str) -> Option<Kind> {
+        Kind::ALL.iter().cloned().find(|x| x.name() == name)
     }
 
-    pub fn is_in_screen(&self) -> bool {
-        self.shape.is_in_screen()
-    }
-
-    pub fn advance(&mut self) {
-        self.shape.advance(&self.v)
+    fn aim(self) -> Aim {
+        match self {
+            Kind::Fighter => Aim::AtPlayer,
+            Kind::Ace => Aim::Leading,
+            Kind::Gunner => Aim::Spread(GUNNER_SPREAD),
+            Kind::Bomber => Aim::Angle(BOMBER_ANGLE),
+        }
     }
+}
 
-    pub fn die(&mut self) {
-        self.alive = false;
-    }
+// An enemy entering at a random height on the right edge of the screen
+pub fn spawn_random<'a, 'b, R: Rng>(
+    world: &mut World<'a, 'b>,
+    sprites: &Sprites<'a, 'b>,
+    rng: &mut R,
+    modifiers: &Modifiers,
+) -> Entity {
+    const MAX_SPEED: f64 = 4.0;
+    const MIN_SPEED: f64 = 1.0;
+    let (w, h) = sprites.enemy_ship.atlas().frame_size("fly");
+    let speed = rng.gen_range(MIN_SPEED, MAX_SPEED + 1.0) * modifiers.speed;
+    // mostly plain fighters, aces are rare
+    let kind = match rng.gen_range(0, 20) {
+        0..=9 => Kind::Fighter,
+        10..=13 => Kind::Gunner,
+        14..=16 => Kind::Bomber,
+        _ => Kind::Ace,
+    };
+    spawn(
+        world,
+        sprites,
+        kind,
+        (WIDTH - w) as i32,
+        rng.gen_range(1, HEIGHT - h) as i32,
+        speed,
+        modifiers.fire_rate,
+    )
+}
 
-    pub fn is_alive(&self) -> bool {
-        self.alive
-    }
+// An enemy flying left at the given speed, shooting at the player. A fire
+// rate of 2.0 shoots twice as often as usual.
+pub fn spawn<'a, 'b>(
+    world: &mut World<'a, 'b>,
+    sprites: &Sprites<'a, 'b>,
+    kind: Kind,
+    x: i32,
+    y: i32,
+    speed: f64,
+    fire_rate: f64,
+) -> Entity {
+    let (w, h) = sprites.enemy_ship.atlas().frame_size("fly");
+    let entity = world.spawn();
+    world
+        .positions
+        .insert(entity, Vec2::new(x as f64, y as f64));
+    world.velocities.insert(entity, Vec2::new(-speed, 0.0));
+    world
+        .shapes
+        .insert(entity, Shape::new_rectangle(0, 0, w, h));
+    world
+        .sprites
+        .insert(entity, Sprite::new(sprites.enemy_ship, "fly"));
+    world.healths.insert(entity, Health::new(1));
+    world.layers.insert(entity, Layer::Enemy);
+    world.weapons.insert(
+        entity,
+        Weapon {
+            sprite: sprites.enemy_shot,
+            speed: -ENEMY_PROJECTILE_SPEED,
+            cooldown: SHOOT_FREQ.div_f64(fire_rate),
+            last_shot: None,
+            shots: 1,
+            aim: kind.aim(),
+            shape: ProjShape::Circle,
+            layer: Layer::EnemyShot,
+            trigger: true,
+        },
+    );
+    entity
 }
@@ -0,0 +1,78 @@
+// Files kept for the player between runs, like the settings and the save.
+
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+// One of the player's files, in the directory the XDG variable names, or
+// under home_dir in $HOME if it's not set, or in %APPDATA% on Windows
+fn player_file(xdg_var: &str, home_dir: &str, name: &str) -> Option<PathBuf> {
+    let dir = env::var_os(xdg_var)
+        .filter(|x| !x.is_empty())
+        .map(PathBuf::from)
+        .or_else(|| env::var_os("HOME").map(|x| PathBuf::from(x).join(home_dir)))
+        .or_else(|| env::var_os("APPDATA").map(PathBuf::from))?;
+    Some(dir.join("sideways").join(name))
+}
+
+// A file in $XDG_CONFIG_HOME/sideways, which is usually ~/.config/sideways
+pub fn config_file(name: &str) -> Option<PathBuf> {
+    player_file("XDG_CONFIG_HOME", ".config", name)
+}
+
+// A file in $XDG_DATA_HOME/sideways, which is usually ~/.local/share/sideways
+pub fn data_file(name: &str) -> Option<PathBuf> {
+    player_file("XDG_DATA_HOME", ".local/share", name)
+}
+
+// The path with something added to the end of the file name
+fn with_suffix(path: &Path, suffix: &str) -> PathBuf {
+    let mut name = path.as_os_str().to_owned();
+    name.push(suffix);
+    PathBuf::from(name)
+}
+
+// Written to a temporary file first and then moved into place, so the game
+// stopping halfway through never leaves half a file
+pub fn write(path: &Path, contents: &str) -> Result<(), String> {
+    let err = |e: std::io::Error| format!("{}: {}", path.display(), e);
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir).map_err(err)?;
+    }
+    let temporary = with_suffix(path, ".new");
+    fs::write(&temporary, contents).map_err(err)?;
+    fs::rename(&temporary, path).map_err(err)
+}
+
+// Move a file that can't be read out of the way, so saving over it doesn't
+// lose what's in it. Returns where it went.
+pub fn move_aside(path: &Path) -> Option<PathBuf> {
+    let backup = with_suffix(path, ".bad");
+    fs::rename(path, &backup).ok().map(|_| backup)
+}
+
+// A file of its own in the temporary directory for a test, gone afterwards
+// along with anything moved aside
+#[cfg(test)]
+pub struct TempFile(pub PathBuf);
+
+#[cfg(test)]
+impl TempFile {
+    pub fn new(name: &str, contents: &[u8]) -> Self {
+        let path = env::temp_dir().join(format!("sideways-{}-{}", std::process::id(), name));
+        fs::write(&path, contents).unwrap();
+        TempFile(path)
+    }
+
+    pub fn backup(&self) -> PathBuf {
+        with_suffix(&self.0, ".bad")
+    }
+}
+
+#[cfg(test)]
+impl Drop for TempFile {
+    fn drop(&mut self) {
+        let _ = fs::remove_file(&self.0);
+        let _ = fs::remove_file(self.backup());
+    }
+}
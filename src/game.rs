@@ -0,0 +1,490 @@
+use crate::animation::SpriteSheet;
+use crate::audio::{Audio, Sound};
+use crate::debug::{self, Counts};
+use crate::difficulty::{self, Difficulty, Modifiers, Rank};
+use crate::enemy;
+use crate::handling::Handling;
+use crate::input::Input;
+use crate::layers::{CollisionMatrix, Layer};
+use crate::particles::{Emitter, ParticleSystem};
+use crate::player;
+use crate::systems::{self, Scratch, Target};
+use crate::text::{self, TextBuffer};
+use crate::vec2::Vec2;
+use crate::world::{Entity, World};
+use crate::{ENEMY_SPAWN_CHANCE, FRAME_TIME, HEIGHT, WIDTH};
+use rand::prelude::*;
+use rand::rngs::StdRng;
+use sdl2::pixels::Color;
+use sdl2::render::Canvas;
+use sdl2::video::Window;
+use std::f64::consts::PI;
+use std::fmt::Write;
+use std::time::Duration;
+
+const STARTING_LIVES: u32 = 3;
+const SCORE_PER_KILL: u32 = 100;
+const INVULNERABLE_TIME: Duration = Duration::from_secs(2);
+pub const MAX_WEAPON: u32 = 5;
+const ENEMY_SPEED: f64 = 2.0;
+
+#[derive(Clone, Copy)]
+pub struct Sprites<'a, 'b> {
+    pub player_ship: &'a SpriteSheet<'b>,
+    pub player_shot: &'a SpriteSheet<'b>,
+    pub enemy_ship: &'a SpriteSheet<'b>,
+    pub enemy_shot: &'a SpriteSheet<'b>,
+}
+
+// Everything that changes how the game plays: the data files, which can be
+// reloaded while it runs, and the difficulty chosen in the options menu
+#[derive(Debug, Clone, Copy)]
+pub struct Rules {
+    pub collision_matrix: CollisionMatrix,
+    pub handling: Handling,
+    pub difficulty: Difficulty,
+    // let the difficulty climb while the player does well
+    pub rank: bool,
+}
+
+// How a new game starts
+#[derive(Debug, Clone, Copy)]
+pub struct Setup {
+    pub seed: u64,
+    // 1, or 2 for co-op
+    pub players: usize,
+    // shots every player's weapon starts with
+    pub weapon: u32,
+}
+
+// One of the people playing, and the ship they fly
+#[derive(Debug, Clone, Copy)]
+struct Player {
+    ship: Entity,
+    score: u32,
+}
+
+// Everything belonging to one play session, from pressing start to game over
+pub struct Game<'a, 'b> {
+    sprites: Sprites<'a, 'b>,
+    // all gameplay randomness comes from here, so a seed replays the same game
+    rng: StdRng,
+    seed: u64,
+    world: World<'a, 'b>,
+    collision_matrix: CollisionMatrix,
+    difficulty: Difficulty,
+    rank_enabled: bool,
+    rank: Rank,
+    players: Vec<Player>,
+    // where the players are, for enemies to aim at
+    targets: Vec<Target>,
+    // Game time advances by one FRAME_TIME per update, so it stands still
+    // while the game is paused
+    clock: Duration,
+    // check once every second whether to spawn new enemy
+    enemy_tick: Duration,
+    // shape tests made during the last update, for the debug overlay
+    collision_checks: usize,
+    scratch: Scratch,
+}
+
+// For rollback (see rollback.rs), which keeps copies of the game from the
+// last few frames. clone_from reuses the copy's memory, so it doesn't allocate.
+impl<'a, 'b> Clone for Game<'a, 'b> {
+    fn clone(&self) -> Self {
+        Game {
+            sprites: self.sprites,
+            rng: self.rng.clone(),
+            seed: self.seed,
+            world: self.world.clone(),
+            collision_matrix: self.collision_matrix,
+            difficulty: self.difficulty,
+            rank_enabled: self.rank_enabled,
+            rank: self.rank,
+            players: self.players.clone(),
+            targets: self.targets.clone(),
+            clock: self.clock,
+            enemy_tick: self.enemy_tick,
+            collision_checks: self.collision_checks,
+            scratch: Scratch::default(),
+        }
+    }
+
+    // The scratch buffers are emptied before every use, so they stay
+    fn clone_from(&mut self, source: &Self) {
+        self.sprites = source.sprites;
+        self.rng.clone_from(&source.rng);
+        self.seed = source.seed;
+        self.world.clone_from(&source.world);
+        self.collision_matrix = source.collision_matrix;
+        self.difficulty = source.difficulty;
+        self.rank_enabled = source.rank_enabled;
+        self.rank = source.rank;
+        self.players.clone_from(&source.players);
+        self.targets.clone_from(&source.targets);
+        self.clock = source.clock;
+        self.enemy_tick = source.enemy_tick;
+        self.collision_checks = source.collision_checks;
+    }
+}
+
+impl<'a, 'b> Game<'a, 'b> {
+    pub fn new(sprites: Sprites<'a, 'b>, setup: Setup, rules: Rules) -> Self {
+        let mut world = World::new();
+        // spread the ships out evenly from top to bottom
+        let players = (0..setup.players)
+            .map(|i| {
+                let y = HEIGHT as f64 * (i + 1) as f64 / (setup.players + 1) as f64;
+                let ship =
+                    player::spawn(&mut world, &sprites, i, y, STARTING_LIVES, rules.handling);
+                if let Some(weapon) = world.weapons.get_mut(ship) {
+                    weapon.shots = setup.weapon;
+                }
+                Player { ship, score: 0 }
+            })
+            .collect();
+        Game {
+            sprites,
+            rng: StdRng::seed_from_u64(setup.seed),
+            seed: setup.seed,
+            world,
+            collision_matrix: rules.collision_matrix,
+            difficulty: rules.difficulty,
+            rank_enabled: rules.rank,
+            rank: Rank::default(),
+            players,
+            targets: Vec::with_capacity(setup.players),
+            clock: Duration::from_secs(0),
+            enemy_tick: Duration::from_secs(0),
+            collision_checks: 0,
+            scratch: Scratch::default(),
+        }
+    }
+
+    pub fn seed(&self) -> u64 {
+        self.seed
+    }
+
+    pub fn set_rules(&mut self, rules: Rules) {
+        self.collision_matrix = rules.collision_matrix;
+        self.difficulty = rules.difficulty;
+        self.rank_enabled = rules.rank;
+        for player in &self.players {
+            if let Some(handling) = self.world.handlings.get_mut(player.ship) {
+                *handling = rules.handling;
+            }
+        }
+    }
+
+    pub fn players(&self) -> usize {
+        self.players.len()
+    }
+
+    pub fn score(&self, player: usize) -> u32 {
+        self.players[player].score
+    }
+
+    pub fn lives(&self, player: usize) -> u32 {
+        let ship = self.players[player].ship;
+        self.world.healths.get(ship).map_or(0, |x| x.hp)
+    }
+
+    // Rank between 0.0 and 1.0, or None when it's turned off
+    pub fn rank(&self) -> Option<f64> {
+        if !self.rank_enabled {
+            return None;
+        }
+        let weapon_level = self
+            .players
+            .iter()
+            .filter_map(|x| self.world.weapons.get(x.ship))
+            .map(|x| x.shots)
+            .max()
+            .unwrap_or(1);
+        Some(self.rank.value(weapon_level))
+    }
+
+    fn modifiers(&self) -> Modifiers {
+        difficulty::modifiers(self.difficulty, self.rank())
+    }
+
+    // Over when everybody is out of lives
+    pub fn is_over(&self) -> bool {
+        (0..self.players.len()).all(|i| self.lives(i) == 0)
+    }
+
+    pub fn counts(&self) -> Counts {
+        Counts {
+            enemies: self
+                .world
+                .layers
+                .iter()
+                .filter(|&(_, &x)| x == Layer::Enemy)
+                .count(),
+            projectiles: self
+                .world
+                .layers
+                .iter()
+                .filter(|&(_, x)| x.is_shot())
+                .count(),
+            // the particle system lives outside the game
+            particles: 0,
+            collision_checks: self.collision_checks,
+            // measured by the main loop
+            allocations: 0,
+            rank: self.rank(),
+            // filled in by the main loop in network games
+            rollback: None,
+        }
+    }
+
+    pub fn spawn_enemy(&mut self, kind: enemy::Kind, x: i32, y: i32) {
+        let modifiers = self.modifiers();
+        enemy::spawn(
+            &mut self.world,
+            &self.sprites,
+            kind,
+            x,
+            y,
+            ENEMY_SPEED * modifiers.speed,
+            modifiers.fire_rate,
+        );
+    }
+
+    // Toggle invincibility for every player, returning whether it's now on
+    pub fn toggle_god(&mut self) -> bool {
+        let ships = self.players.iter().map(|x| x.ship);
+        let on = !ships
+            .clone()
+            .filter_map(|x| self.world.healths.get(x))
+            .any(|x| x.invincible);
+        for ship in ships {
+            if let Some(health) = self.world.healths.get_mut(ship) {
+                health.invincible = on;
+            }
+        }
+        on
+    }
+
+    // Every player fires n shots at once, with a fanfare if that's more
+    // than before
+    pub fn give_weapon(&mut self, n: u32, audio: &Audio) {
+        let mut upgraded = false;
+        for player in &self.players {
+            if let Some(weapon) = self.world.weapons.get_mut(player.ship) {
+                upgraded |= n > weapon.shots;
+                weapon.shots = n;
+            }
+        }
+        if upgraded {
+            audio.play(Sound::PowerUp);
+        }
+    }
+
+    // Steer a player's ship and pull its trigger
+    fn handle_input(&mut self, ship: Entity, input: &Input) {
+        if let (Some(v), Some(handling)) = (
+            self.world.velocities.get_mut(ship),
+            self.world.handlings.get(ship),
+        ) {
+            *v = handling.steer(*v, input.direction(), input.focus);
+        }
+
+        if let Some(weapon) = self.world.weapons.get_mut(ship) {
+            weapon.trigger = input.fire;
+        }
+    }
+
+    // Advance the game by one frame, with what each player is holding down
+    pub fn update(&mut self, inputs: &[Input], audio: &Audio, particles: &mut ParticleSystem) {
+        self.clock += FRAME_TIME;
+        let now = self.clock;
+        self.rank.survive();
+        let modifiers = self.modifiers();
+
+        for i in 0..self.players.len() {
+            let ship = self.players[i].ship;
+            self.handle_input(ship, &inputs.get(i).cloned().unwrap_or_default());
+            player::bank(&mut self.world, ship);
+        }
+        systems::movement(&mut self.world);
+        systems::animate(&mut self.world);
+        for player in &self.players {
+            if let Some(shape) = self.world.world_shape(player.ship) {
+                let player_box = shape.get_box();
+                particles.emit(
+                    &Emitter::exhaust(),
+                    player_box.x as f64,
+                    (player_box.y + player_box.height as i32 / 2) as f64,
+                );
+            }
+        }
+
+        if now - self.enemy_tick >= Duration::from_secs(1)
+            && self.rng.gen::<f64>() < ENEMY_SPAWN_CHANCE * modifiers.spawn_chance
+        {
+            enemy::spawn_random(&mut self.world, &self.sprites, &mut self.rng, &modifiers);
+            self.enemy_tick = now;
+        }
+
+        self.targets.clear();
+        for player in &self.players {
+            if let Some((x, y)) = self.world.center(player.ship) {
+                self.targets.push(Target {
+                    position: Vec2::new(x as f64, y as f64),
+                    velocity: self
+                        .world
+                        .velocities
+                        .get(player.ship)
+                        .cloned()
+                        .unwrap_or_default(),
+                });
+            }
+        }
+        let shooters = systems::fire(
+            &mut self.world,
+            &mut self.scratch,
+            now,
+            &self.targets,
+            &mut self.rng,
+        );
+        for &shooter in shooters {
+            audio.play(if self.players.iter().any(|x| x.ship == shooter) {
+                Sound::Shoot
+            } else {
+                Sound::EnemyShoot
+            });
+        }
+
+        let mut collision_checks = 0;
+        // hits live in the scratch buffers, so borrow them back for the loop
+        let mut scratch = std::mem::take(&mut self.scratch);
+        let hits = systems::collisions(
+            &mut self.world,
+            &mut scratch,
+            &self.collision_matrix,
+            now,
+            &mut collision_checks,
+        );
+        for hit in hits {
+            if hit.hurt_a {
+                self.hit(hit.a, hit.b, audio, particles);
+            }
+            if hit.hurt_b {
+                self.hit(hit.b, hit.a, audio, particles);
+            }
+        }
+        self.collision_checks = collision_checks;
+
+        systems::despawn(&mut self.world, &mut scratch);
+        self.scratch = scratch;
+    }
+
+    // Effects of an entity being hit by another, after it has lost its hit point
+    fn hit(&mut self, entity: Entity, by: Entity, audio: &Audio, particles: &mut ParticleSystem) {
+        let (x, y) = match self.world.center(entity) {
+            Some((x, y)) => (x as f64, y as f64),
+            None => return,
+        };
+        let layer = self.world.layers.get(entity).cloned();
+        if layer == Some(Layer::PlayerShot) {
+            particles.emit(&Emitter::impact(), x, y);
+        } else if layer == Some(Layer::EnemyShot) {
+            particles.emit(&Emitter::sparks().towards(PI), x, y);
+        } else if let Some(player) = self.players.iter().position(|x| x.ship == entity) {
+            self.player_hit(player, audio, particles);
+        } else if layer != Some(Layer::Pickup)
+            && self.world.healths.get(entity).is_some_and(|x| x.hp == 0)
+        {
+            particles.emit(&Emitter::explosion(), x, y);
+            // only shooting something down scores, not crashing into it
+            if self.world.layers.get(by) == Some(&Layer::PlayerShot) {
+                // whoever fired the shot gets the points
+                let owner = self.world.owners.get(by).cloned();
+                if let Some(player) = self.players.iter_mut().find(|x| Some(x.ship) == owner) {
+                    player.score += SCORE_PER_KILL;
+                }
+                self.rank.kill();
+                particles.emit(&Emitter::debris(), x, y);
+            }
+            audio.play(Sound::Explosion);
+        }
+    }
+
+    fn player_hit(&mut self, player: usize, audio: &Audio, particles: &mut ParticleSystem) {
+        audio.play(Sound::Hit);
+        self.rank.die();
+        let ship = self.players[player].ship;
+        let (x, y) = self.world.center(ship).unwrap_or((0, 0));
+        particles.emit(&Emitter::sparks(), x as f64, y as f64);
+        if let Some(health) = self.world.healths.get_mut(ship) {
+            if health.hp == 0 {
+                particles.emit(&Emitter::explosion(), x as f64, y as f64);
+                particles.emit(&Emitter::debris(), x as f64, y as f64);
+            }
+            health.invulnerable_until = self.clock + INVULNERABLE_TIME;
+        }
+    }
+
+    pub fn draw(&self, canvas: &mut Canvas<Window>) -> Result<(), String> {
+        systems::draw(&self.world, canvas, self.clock)
+    }
+
+    // Collision shapes and velocities of everything in the game, coloured by
+    // collision layer
+    pub fn draw_debug(&self, canvas: &mut Canvas<Window>) -> Result<(), String> {
+        let velocity_color = Color::RGB(0xff, 0xff, 0x00);
+        for (entity, _) in self.world.shapes.iter() {
+            let shape = match self.world.world_shape(entity) {
+                Some(shape) => shape,
+                None => continue,
+            };
+            let color = match self.world.layers.get(entity) {
+                Some(Layer::Player) => Color::RGB(0x40, 0xff, 0x40),
+                Some(Layer::PlayerShot) => Color::RGB(0x40, 0xc0, 0xff),
+                Some(Layer::Enemy) => Color::RGB(0xff, 0x40, 0x40),
+                Some(Layer::EnemyShot) => Color::RGB(0xff, 0x40, 0xff),
+                Some(Layer::Terrain) => Color::RGB(0xa0, 0xa0, 0xa0),
+                Some(Layer::Pickup) => Color::RGB(0xff, 0xff, 0xff),
+                Some(Layer::Hazard) => Color::RGB(0xff, 0xa0, 0x20),
+                None => Color::RGB(0x60, 0x60, 0x60),
+            };
+            debug::draw_shape(canvas, &shape, color)?;
+            if let Some(v) = self.world.velocities.get(entity) {
+                debug::draw_velocity(canvas, &shape, v, velocity_color)?;
+            }
+        }
+        Ok(())
+    }
+
+    // Score on the left and lives on the right, or with two players each
+    // player's score and lives in their own corner
+    pub fn draw_hud(&self, canvas: &mut Canvas<Window>) -> Result<(), String> {
+        let color = Color::RGB(0xff, 0xff, 0xff);
+        let right =
+            |text: &TextBuffer| WIDTH as i32 - 4 - text::text_width(text.as_str(), 1) as i32;
+        if self.players.len() == 1 {
+            let mut score = TextBuffer::new();
+            write!(score, "SCORE {:06}", self.score(0)).ok();
+            text::draw_text(canvas, score.as_str(), 4, 4, 1, color)?;
+            let mut lives = TextBuffer::new();
+            write!(lives, "LIVES {}", self.lives(0)).ok();
+            return text::draw_text(canvas, lives.as_str(), right(&lives), 4, 1, color);
+        }
+        for i in 0..self.players.len() {
+            let mut status = TextBuffer::new();
+            write!(
+                status,
+                "{}P {:06} LIVES {}",
+                i + 1,
+                self.score(i),
+                self.lives(i)
+            )
+            .ok();
+            let x = if i % 2 == 0 { 4 } else { right(&status) };
+            let y = 4 + (i / 2) as i32 * (text::GLYPH_HEIGHT as i32 + 3);
+            text::draw_text(canvas, status.as_str(), x, y, 1, color)?;
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,88 @@
+use crate::assets::Assets;
+use crate::vec2::Vec2;
+
+// How a ship accelerates, slows down and how fast it can go, in pixels per
+// frame. Loaded from a text file with one setting per line:
+//
+//   acceleration <speed gained per frame while a direction is held>
+//   drag <fraction of speed kept per frame on an axis with nothing held>
+//   max_speed <top speed, in any direction>
+//   focus <speed multiplier while the focus key is held>
+//
+// Settings that are left out keep their default values. Empty lines and lines
+// starting with # are ignored.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Handling {
+    pub acceleration: f64,
+    pub drag: f64,
+    pub max_speed: f64,
+    pub focus: f64,
+}
+
+impl Handling {
+    pub fn parse(source: &str) -> Result<Self, String> {
+        let mut handling = Handling::default();
+        for (line_no, line) in source.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let err = |msg: &str| format!("line {}: {}", line_no + 1, msg);
+            let words: Vec<&str> = line.split_whitespace().collect();
+            if words.len() != 2 {
+                return Err(err("expected <setting> <value>"));
+            }
+            let value = words[1].parse::<f64>().map_err(|_| err("invalid number"))?;
+            let (setting, valid) = match words[0] {
+                "acceleration" => (&mut handling.acceleration, value > 0.0),
+                "drag" => (&mut handling.drag, (0.0..=1.0).contains(&value)),
+                "max_speed" => (&mut handling.max_speed, value > 0.0),
+                "focus" => (&mut handling.focus, value > 0.0 && value <= 1.0),
+                name => return Err(err(&format!("unknown setting {}", name))),
+            };
+            if !valid {
+                return Err(err(&format!("{} out of range", words[0])));
+            }
+            *setting = value;
+        }
+        Ok(handling)
+    }
+
+    pub fn load(assets: &Assets, path: &str) -> Result<Self, String> {
+        let source = assets.read_to_string(path)?;
+        Handling::parse(&source).map_err(|e| format!("{}: {}", path, e))
+    }
+
+    // Velocity after one frame of holding the given direction, where each
+    // axis is -1, 0 or 1. Speed is limited the same in every direction, so
+    // moving diagonally isn't any faster.
+    pub fn steer(&self, v: Vec2, direction: Vec2, focus: bool) -> Vec2 {
+        let scale = if focus { self.focus } else { 1.0 };
+        let acceleration = self.acceleration * scale;
+        let max_speed = self.max_speed * scale;
+        let axis = |v: f64, held: f64| {
+            if held == 0.0 {
+                v * self.drag
+            } else {
+                v + held * acceleration
+            }
+        };
+        let v = Vec2::new(axis(v.x, direction.x), axis(v.y, direction.y));
+        if v.length() > max_speed {
+            v.normalize() * max_speed
+        } else {
+            v
+        }
+    }
+}
+
+impl Default for Handling {
+    fn default() -> Self {
+        Handling {
+            acceleration: 2.0,
+            drag: 0.7,
+            max_speed: 6.0,
+            focus: 0.4,
+        }
+    }
+}
@@ -0,0 +1,157 @@
+use crate::vec2::Vec2;
+use sdl2::controller::{Axis, Button, GameController};
+use sdl2::keyboard::Keycode;
+use std::collections::HashSet;
+
+pub const MAX_PLAYERS: usize = 2;
+
+// How far a stick has to be pushed to count, out of 32767
+const STICK_DEAD_ZONE: i16 = 8000;
+
+// What one player is holding down during a frame
+#[derive(Debug, Clone, Copy, Default, Eq, PartialEq)]
+pub struct Input {
+    pub up: bool,
+    pub down: bool,
+    pub left: bool,
+    pub right: bool,
+    pub fire: bool,
+    // move slowly, for dodging through tight gaps
+    pub focus: bool,
+}
+
+impl Input {
+    // -1, 0 or 1 on each axis. Opposite directions cancel out.
+    pub fn direction(&self) -> Vec2 {
+        let axis = |negative, positive| match (negative, positive) {
+            (true, false) => -1.0,
+            (false, true) => 1.0,
+            _ => 0.0,
+        };
+        Vec2::new(axis(self.left, self.right), axis(self.up, self.down))
+    }
+
+    // One bit per button, for sending over the network
+    pub fn to_bits(self) -> u8 {
+        [
+            self.up, self.down, self.left, self.right, self.fire, self.focus,
+        ]
+        .iter()
+        .enumerate()
+        .fold(0, |bits, (i, &held)| bits | (held as u8) << i)
+    }
+
+    pub fn from_bits(bits: u8) -> Self {
+        let held = |i: u8| bits & 1 << i != 0;
+        Input {
+            up: held(0),
+            down: held(1),
+            left: held(2),
+            right: held(3),
+            fire: held(4),
+            focus: held(5),
+        }
+    }
+
+    // Add whatever is held on a gamepad: the D-pad or left stick to move,
+    // A or X to fire and a shoulder button to focus
+    pub fn add_controller(&mut self, controller: &GameController) {
+        let (x, y) = (controller.axis(Axis::LeftX), controller.axis(Axis::LeftY));
+        self.up |= controller.button(Button::DPadUp) || y < -STICK_DEAD_ZONE;
+        self.down |= controller.button(Button::DPadDown) || y > STICK_DEAD_ZONE;
+        self.left |= controller.button(Button::DPadLeft) || x < -STICK_DEAD_ZONE;
+        self.right |= controller.button(Button::DPadRight) || x > STICK_DEAD_ZONE;
+        self.fire |= controller.button(Button::A) || controller.button(Button::X);
+        self.focus |=
+            controller.button(Button::LeftShoulder) || controller.button(Button::RightShoulder);
+    }
+}
+
+// Keys for one player. Any of the keys listed for an action will do.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Controls {
+    pub up: Vec<Keycode>,
+    pub down: Vec<Keycode>,
+    pub left: Vec<Keycode>,
+    pub right: Vec<Keycode>,
+    pub fire: Vec<Keycode>,
+    pub focus: Vec<Keycode>,
+}
+
+impl Controls {
+    // Names of the actions, as written in the settings file
+    pub const ACTIONS: [&'static str; 6] = ["up", "down", "left", "right", "fire", "focus"];
+
+    pub fn keys(&self, action: &str) -> Option<&[Keycode]> {
+        let keys = match action {
+            "up" => &self.up,
+            "down" => &self.down,
+            "left" => &self.left,
+            "right" => &self.right,
+            "fire" => &self.fire,
+            "focus" => &self.focus,
+            _ => return None,
+        };
+        Some(keys.as_slice())
+    }
+
+    pub fn keys_mut(&mut self, action: &str) -> Option<&mut Vec<Keycode>> {
+        match action {
+            "up" => Some(&mut self.up),
+            "down" => Some(&mut self.down),
+            "left" => Some(&mut self.left),
+            "right" => Some(&mut self.right),
+            "fire" => Some(&mut self.fire),
+            "focus" => Some(&mut self.focus),
+            _ => None,
+        }
+    }
+
+    // Playing alone: arrow keys, S or Space to fire and either Shift to focus
+    pub fn solo() -> Self {
+        Controls {
+            up: vec![Keycode::Up],
+            down: vec![Keycode::Down],
+            left: vec![Keycode::Left],
+            right: vec![Keycode::Right],
+            fire: vec![Keycode::S, Keycode::Space],
+            focus: vec![Keycode::LShift, Keycode::RShift],
+        }
+    }
+
+    // The first of two players, on the right side of the keyboard
+    pub fn player1() -> Self {
+        Controls {
+            up: vec![Keycode::Up],
+            down: vec![Keycode::Down],
+            left: vec![Keycode::Left],
+            right: vec![Keycode::Right],
+            fire: vec![Keycode::RCtrl, Keycode::Slash],
+            focus: vec![Keycode::RShift],
+        }
+    }
+
+    // The second of two players, on the left side of the keyboard
+    pub fn player2() -> Self {
+        Controls {
+            up: vec![Keycode::W],
+            down: vec![Keycode::S],
+            left: vec![Keycode::A],
+            right: vec![Keycode::D],
+            fire: vec![Keycode::Space, Keycode::F],
+            focus: vec![Keycode::LShift],
+        }
+    }
+
+    pub fn read(&self, keys: &HashSet<Keycode>) -> Input {
+        let held = |bound: &[Keycode]| bound.iter().any(|x| keys.contains(x));
+        Input {
+            up: held(&self.up),
+            down: held(&self.down),
+            left: held(&self.left),
+            right: held(&self.right),
+            fire: held(&self.fire),
+            focus: held(&self.focus),
+        }
+    }
+}
@@ -0,0 +1,148 @@
+use crate::assets::Assets;
+
+// Which collision layer an entity is on. The collision matrix decides what
+// happens when entities on two layers touch.
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Hash)]
+pub enum Layer {
+    Player,
+    PlayerShot,
+    Enemy,
+    EnemyShot,
+    Terrain,
+    Pickup,
+    // obstacles that belong to nobody, like asteroids
+    Hazard,
+}
+
+const LAYER_COUNT: usize = 7;
+
+impl Layer {
+    pub const ALL: [Layer; LAYER_COUNT] = [
+        Layer::Player,
+        Layer::PlayerShot,
+        Layer::Enemy,
+        Layer::EnemyShot,
+        Layer::Terrain,
+        Layer::Pickup,
+        Layer::Hazard,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Layer::Player => "player",
+            Layer::PlayerShot => "player_shot",
+            Layer::Enemy => "enemy",
+            Layer::EnemyShot => "enemy_shot",
+            Layer::Terrain => "terrain",
+            Layer::Pickup => "pickup",
+            Layer::Hazard => "hazard",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Layer> {
+        Layer::ALL.iter().cloned().find(|x| x.name() == name)
+    }
+
+    pub fn is_shot(self) -> bool {
+        self == Layer::PlayerShot || self == Layer::EnemyShot
+    }
+}
+
+// What happens to an entity when it touches one on another layer
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum Contact {
+    Ignore,
+    // lose a hit point
+    Hurt,
+    // bounce off, keeping its hit points
+    Bounce,
+}
+
+// For every pair of layers, what happens to an entity on the first layer when
+// it touches one on the second. Loaded from a text file, one rule per line:
+//
+//   <layer> <other layer> <hurt|bounce|ignore>
+//
+// Pairs without a rule are ignored. Empty lines and lines starting with # are
+// ignored too.
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub struct CollisionMatrix {
+    contacts: [[Contact; LAYER_COUNT]; LAYER_COUNT],
+}
+
+impl CollisionMatrix {
+    pub fn empty() -> Self {
+        CollisionMatrix {
+            contacts: [[Contact::Ignore; LAYER_COUNT]; LAYER_COUNT],
+        }
+    }
+
+    pub fn set(&mut self, layer: Layer, other: Layer, contact: Contact) {
+        self.contacts[layer as usize][other as usize] = contact;
+    }
+
+    pub fn get(&self, layer: Layer, other: Layer) -> Contact {
+        self.contacts[layer as usize][other as usize]
+    }
+
+    pub fn parse(source: &str) -> Result<Self, String> {
+        let mut matrix = CollisionMatrix::empty();
+        for (line_no, line) in source.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let err = |msg: &str| format!("line {}: {}", line_no + 1, msg);
+            let words: Vec<&str> = line.split_whitespace().collect();
+            if words.len() != 3 {
+                return Err(err("expected <layer> <other layer> <hurt|bounce|ignore>"));
+            }
+            let layer = |name: &str| {
+                Layer::from_name(name).ok_or_else(|| err(&format!("unknown layer {}", name)))
+            };
+            let contact = match words[2] {
+                "hurt" => Contact::Hurt,
+                "bounce" => Contact::Bounce,
+                "ignore" => Contact::Ignore,
+                _ => return Err(err("contact must be hurt, bounce or ignore")),
+            };
+            matrix.set(layer(words[0])?, layer(words[1])?, contact);
+        }
+        Ok(matrix)
+    }
+
+    pub fn load(assets: &Assets, path: &str) -> Result<Self, String> {
+        let source = assets.read_to_string(path)?;
+        CollisionMatrix::parse(&source).map_err(|e| format!("{}: {}", path, e))
+    }
+}
+
+impl Default for CollisionMatrix {
+    // Ships crash into ships of the other side, shots hit the other side's
+    // ships, and terrain and hazards get in everybody's way
+    fn default() -> Self {
+        use Layer::*;
+        let mut matrix = CollisionMatrix::empty();
+        for &(layer, other) in &[
+            (Player, Enemy),
+            (Player, EnemyShot),
+            (Player, Terrain),
+            (Player, Hazard),
+            (Enemy, Player),
+            (Enemy, PlayerShot),
+            (Enemy, Hazard),
+            (PlayerShot, Enemy),
+            (PlayerShot, Terrain),
+            (PlayerShot, Hazard),
+            (EnemyShot, Player),
+            (EnemyShot, Terrain),
+            (Hazard, Player),
+            (Hazard, PlayerShot),
+            (Hazard, Enemy),
+            (Pickup, Player),
+        ] {
+            matrix.set(layer, other, Contact::Hurt);
+        }
+        matrix
+    }
+}
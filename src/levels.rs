@@ -0,0 +1,117 @@
+use crate::assets::Assets;
+
+// Something scoring enough in a level unlocks, besides the next level
+#[derive(Debug, Clone, PartialEq)]
+pub enum Unlock {
+    // starting weapon firing this many shots
+    Weapon(u32),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Level {
+    pub name: String,
+    // score that clears the level
+    pub target: u32,
+    pub music: String,
+    pub unlocks: Vec<Unlock>,
+}
+
+// Every level in the order they unlock, loaded from a text file with one
+// level per line:
+//
+//   <level> <target score> [music <track>] [weapon <shots>]
+//
+// Empty lines and lines starting with # are ignored.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Levels {
+    levels: Vec<Level>,
+}
+
+impl Levels {
+    // Just the one level that never unlocks anything, for when the list
+    // can't be loaded
+    pub fn single(name: &str) -> Self {
+        Levels {
+            levels: vec![Level {
+                name: name.to_string(),
+                target: 0,
+                music: name.to_string(),
+                unlocks: vec![],
+            }],
+        }
+    }
+
+    pub fn parse(source: &str) -> Result<Self, String> {
+        let mut levels = vec![];
+        for (line_no, line) in source.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let err = |msg: &str| format!("line {}: {}", line_no + 1, msg);
+            let words: Vec<&str> = line.split_whitespace().collect();
+            if words.len() < 2 || words.len() % 2 != 0 {
+                return Err(err("expected <level> <target score> [<unlock> <value>]..."));
+            }
+            let mut level = Level {
+                name: words[0].to_string(),
+                target: words[1].parse().map_err(|_| err("invalid score"))?,
+                music: words[0].to_string(),
+                unlocks: vec![],
+            };
+            for pair in words[2..].chunks(2) {
+                match pair[0] {
+                    "music" => level.music = pair[1].to_string(),
+                    "weapon" => match pair[1].parse() {
+                        Ok(shots) if (1..=crate::game::MAX_WEAPON).contains(&shots) => {
+                            level.unlocks.push(Unlock::Weapon(shots))
+                        }
+                        _ => {
+                            return Err(err(&format!(
+                                "weapons go from 1 to {}",
+                                crate::game::MAX_WEAPON
+                            )))
+                        }
+                    },
+                    other => return Err(err(&format!("unknown unlock {}", other))),
+                }
+            }
+            if levels.iter().any(|x: &Level| x.name == level.name) {
+                return Err(err(&format!("{} is listed twice", level.name)));
+            }
+            levels.push(level);
+        }
+        if levels.is_empty() {
+            return Err("no levels".to_string());
+        }
+        Ok(Levels { levels })
+    }
+
+    pub fn load(assets: &Assets, path: &str) -> Result<Self, String> {
+        let source = assets.read_to_string(path)?;
+        Levels::parse(&source).map_err(|e| format!("{}: {}", path, e))
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &Level> {
+        self.levels.iter()
+    }
+
+    pub fn first(&self) -> &Level {
+        &self.levels[0]
+    }
+
+    pub fn get(&self, name: &str) -> Option<&Level> {
+        self.levels.iter().find(|x| x.name == name)
+    }
+
+    // The level unlocked by clearing the named one
+    pub fn after(&self, name: &str) -> Option<&Level> {
+        let i = self.levels.iter().position(|x| x.name == name)?;
+        self.levels.get(i + 1)
+    }
+
+    // Music for a level, which for levels not on the list is named after it
+    pub fn music<'s>(&'s self, name: &'s str) -> &'s str {
+        self.get(name).map_or(name, |x| x.music.as_str())
+    }
+}
@@ -0,0 +1,51 @@
+// The game itself. main.rs runs it in a window, and the benchmarks drive
+// parts of it directly.
+
+pub mod alloc_count;
+pub mod animation;
+pub mod assets;
+pub mod audio;
+pub mod cli;
+pub mod collide;
+pub mod components;
+pub mod console;
+pub mod debug;
+pub mod difficulty;
+pub mod display;
+#[cfg(feature = "embed-assets")]
+mod embedded;
+pub mod enemy;
+pub mod files;
+pub mod game;
+pub mod handling;
+pub mod input;
+pub mod layers;
+pub mod levels;
+pub mod menu;
+pub mod net;
+pub mod particles;
+pub mod player;
+pub mod pool;
+pub mod projectile;
+pub mod replay;
+pub mod rollback;
+pub mod save;
+pub mod settings;
+pub mod starfield;
+pub mod systems;
+pub mod text;
+pub mod timing;
+pub mod vec2;
+pub mod world;
+
+use std::time::Duration;
+
+pub const HEIGHT: u32 = 384;
+pub const WIDTH: u32 = 512;
+pub const SHOOT_DELAY: u64 = 80; // milliseconds
+pub const PLAYER_PROJECTILE_SPEED: f64 = 10.0;
+pub const ENEMY_SPAWN_CHANCE: f64 = 0.2;
+pub const ENEMY_PROJECTILE_SPEED: f64 = 4.5;
+pub const FRAME_TIME: Duration = Duration::from_millis(20);
+pub const DEFAULT_LEVEL: &str = "level1";
+pub const DEFAULT_SCALE: u32 = 2;
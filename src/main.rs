@@ -1,267 +1,1022 @@
-use rand::prelude::*;
+use sdl2::controller::GameController;
 use sdl2::event::Event;
-use sdl2::image::{InitFlag, LoadTexture};
+use sdl2::image::InitFlag;
 use sdl2::keyboard::Keycode;
 use sdl2::pixels::Color;
-use sdl2::rect::{Point, Rect};
-use sdl2::render::Texture;
 use std::collections::HashSet;
+use std::env;
+use std::path::PathBuf;
 use std::time::{Duration, Instant};
 
-mod collide;
-mod enemy;
-mod player;
-mod projectile;
-mod starfield;
+use sideways::{
+    alloc_count, assets, audio, cli, console, debug, difficulty, display, enemy, game, handling,
+    input, layers, levels, menu, net, particles, replay, rollback, save, settings, starfield, text,
+    timing,
+};
+use sideways::{DEFAULT_LEVEL, HEIGHT, WIDTH};
+
+use assets::Assets;
+use audio::{Audio, Sound};
+use console::{Command, Console};
+use difficulty::Difficulty;
+use display::{Display, WindowMode};
+use handling::Handling;
+use input::Input;
+use layers::CollisionMatrix;
+use levels::Levels;
+use menu::{Choice, Menu, MenuAction};
+use net::Link;
+use rand::prelude::*;
+use replay::Replay;
+use save::SaveData;
+use settings::Settings;
+
+#[global_allocator]
+static ALLOCATOR: alloc_count::CountingAllocator = alloc_count::CountingAllocator;
 
-use collide::Collider;
-use projectile::{LethalTo, ProjShape, Projectile};
+const MAX_HIGH_SCORES: usize = 5;
+const LEVELS: &str = "levels/levels.list";
+const COLLISION_MATRIX: &str = "collision.matrix";
+const PLAYER_HANDLING: &str = "player.handling";
+const HOT_RELOAD_INTERVAL: Duration = Duration::from_secs(1);
+// debug builds: freeze, step one frame, slower, faster
+const DEBUG_TIME_KEYS: [Keycode; 4] = [Keycode::F5, Keycode::F6, Keycode::F7, Keycode::F8];
 
-#[derive(Debug, Clone, Copy)]
-pub struct Velocity {
-    pub x: f64,
-    pub y: f64,
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+enum GameState {
+    Title,
+    LevelSelect { players: usize },
+    // waiting for the other player of a network game
+    Connecting,
+    Playing,
+    Paused,
+    // Options can be reached from both the title screen and the pause menu,
+    // so remember where to go back to
+    Options { from_pause: bool },
+    GameOver,
+    HighScores,
 }
 
-pub enum Axis {
-    X,
-    Y,
+fn display_label(mode: WindowMode) -> String {
+    format!("DISPLAY: {}", mode.label())
 }
 
-impl Velocity {
-    pub fn new(x: f64, y: f64) -> Velocity {
-        Velocity { x, y }
-    }
+fn scale_label(scale: u32) -> String {
+    format!("SCALE: {}X", scale)
+}
 
-    pub fn apply_inertia(&mut self, axis: Axis) {
-        const INERTIA: f64 = 0.7;
-        match axis {
-            Axis::Y => {
-                self.y *= INERTIA;
-            }
-            Axis::X => {
-                self.x *= INERTIA;
-            }
-        }
-    }
+fn scanlines_label(scanlines: bool) -> String {
+    format!("SCANLINES: {}", if scanlines { "ON" } else { "OFF" })
 }
 
-impl std::ops::Add for Velocity {
-    type Output = Self;
-    fn add(self, other: Self) -> Velocity {
-        Velocity {
-            x: self.x + other.x,
-            y: self.y + other.y,
-        }
-    }
+fn difficulty_label(difficulty: Difficulty) -> String {
+    format!("DIFFICULTY: {}", difficulty.label())
 }
 
-impl std::ops::AddAssign for Velocity {
-    fn add_assign(&mut self, other: Self) {
-        *self = Self {
-            x: self.x + other.x,
-            y: self.y + other.y,
-        }
-    }
+fn rank_label(rank: bool) -> String {
+    format!("RANK: {}", if rank { "ON" } else { "OFF" })
+}
+
+fn volume_label(name: &str, volume: u8) -> String {
+    format!("{}: {}", name, volume)
 }
 
-const HEIGHT: u32 = 384;
-const WIDTH: u32 = 512;
-const PLAYER_MAX_SPEED: f64 = 6.0;
-const SHOOT_DELAY: u64 = 80; // milliseconds
-const PLAYER_PROJECTILE_SPEED: f64 = 10.0;
-const ENEMY_SPAWN_CHANCE: f64 = 0.2;
-const ENEMY_PROJECTILE_SPEED: f64 = 4.5;
-
-// Read keyboard input
-//
-// Move the ship with arrow keys
-// Shoot with S or Space
-//
-// Keep track of projectile shooting delay
-fn handle_input<'a, 'b>(
-    keycodes: &HashSet<Keycode>,
-    player: &mut player::Player,
-    projectiles: &mut Vec<Projectile<'a, 'b>>,
-    projectile_texture: &'a Texture<'b>,
-    last_shot: &mut Option<Instant>,
-) {
-    const SPEED: f64 = 2.0; // how much to increment player ship velocity each frame
-    if !(keycodes.contains(&Keycode::Up) || keycodes.contains(&Keycode::Down)) {
-        player.v.apply_inertia(Axis::Y)
+fn adjust_volume(volume: u8, delta: i32) -> u8 {
+    (i32::from(volume) + delta)
+        .max(0)
+        .min(i32::from(audio::MAX_VOLUME)) as u8
+}
+
+fn starfield_path(level: &str) -> String {
+    format!("levels/{}.starfield", level)
+}
+
+fn load_starfield<'b>(assets: &Assets<'b>, level: &str) -> starfield::Starfield<'b> {
+    starfield::Starfield::load(assets, &starfield_path(level)).unwrap_or_else(|e| {
+        eprintln!("Could not load starfield, using the default one: {}", e);
+        starfield::Starfield::new()
+    })
+}
+
+fn load_collision_matrix(assets: &Assets) -> CollisionMatrix {
+    CollisionMatrix::load(assets, COLLISION_MATRIX).unwrap_or_else(|e| {
+        eprintln!(
+            "Could not load collision matrix, using the default one: {}",
+            e
+        );
+        CollisionMatrix::default()
+    })
+}
+
+// Loads the data files, keeping the difficulty settings
+fn load_rules(assets: &Assets, difficulty: Difficulty, rank: bool) -> game::Rules {
+    let handling = Handling::load(assets, PLAYER_HANDLING).unwrap_or_else(|e| {
+        eprintln!("Could not load ship handling, using the default one: {}", e);
+        Handling::default()
+    });
+    game::Rules {
+        collision_matrix: load_collision_matrix(assets),
+        handling,
+        difficulty,
+        rank,
     }
-    if !(keycodes.contains(&Keycode::Left) || keycodes.contains(&Keycode::Right)) {
-        player.v.apply_inertia(Axis::X)
+}
+
+fn new_game<'a, 'b>(
+    sprites: game::Sprites<'a, 'b>,
+    setup: game::Setup,
+    rules: game::Rules,
+    music: &str,
+    assets: &Assets,
+    audio: &mut Audio,
+    particles: &mut particles::ParticleSystem,
+) -> game::Game<'a, 'b> {
+    particles.clear();
+    audio.play_music(assets, music);
+    game::Game::new(sprites, setup, rules)
+}
+
+// Remember the options as they are now for the next run
+fn save_settings(settings: &mut Settings, display: &Display, audio: &Audio, rules: &game::Rules) {
+    settings.scale = display.scale();
+    settings.mode = display.mode();
+    settings.scanlines = display.scanlines();
+    settings.sfx_volume = audio.sfx_volume();
+    settings.music_volume = audio.music_volume();
+    settings.difficulty = rules.difficulty;
+    settings.rank = rules.rank;
+    if let Err(e) = settings.save() {
+        eprintln!("Could not save settings: {}", e);
     }
+}
 
-    for k in keycodes {
-        match k {
-            Keycode::Up => player.v += Velocity::new(0.0, -SPEED),
-            Keycode::Down => player.v += Velocity::new(0.0, SPEED),
-            Keycode::Left => player.v += Velocity::new(-SPEED, 0.0),
-            Keycode::Right => player.v += Velocity::new(SPEED, 0.0),
-            Keycode::S | Keycode::Space => {
-                let now = Instant::now();
-                if (last_shot.is_some()
-                    && now - last_shot.unwrap() > Duration::from_millis(SHOOT_DELAY))
-                    || last_shot.is_none()
-                {
-                    projectiles.push(Projectile::from_sprite(
-                        projectile_texture,
-                        &player.shape,
-                        PLAYER_PROJECTILE_SPEED,
-                        ProjShape::Rectangle,
-                        LethalTo::Enemy,
-                        None,
-                    ));
-                    *last_shot = Some(now);
-                }
+// A line per level with its best score, or whether it's still locked
+fn level_menu(levels: &Levels, progress: &SaveData) -> Menu {
+    let mut items: Vec<String> = levels
+        .iter()
+        .map(|x| {
+            let name = x.name.to_uppercase();
+            match progress.best_score(&x.name) {
+                _ if !progress.is_unlocked(levels, &x.name) => format!("{}  LOCKED", name),
+                Some(score) => format!("{}  BEST {:06}", name, score),
+                None => format!("{}  BEST ------", name),
             }
-            _ => {}
-        };
-    }
+        })
+        .collect();
+    items.push("BACK".to_string());
+    let items: Vec<&str> = items.iter().map(|x| x.as_str()).collect();
+    Menu::new("SELECT LEVEL", &items)
+}
 
-    // limit player ship's maximum speed
-    if player.v.x > PLAYER_MAX_SPEED {
-        player.v.x = PLAYER_MAX_SPEED;
-    } else if player.v.x < -PLAYER_MAX_SPEED {
-        player.v.x = -PLAYER_MAX_SPEED;
+// Keep the best score of a game that ended, and whatever it unlocked
+fn record_progress(progress: &mut SaveData, levels: &Levels, level: &str, g: &game::Game) {
+    let score = (0..g.players()).map(|i| g.score(i)).max().unwrap_or(0);
+    if progress.record(levels, level, score) {
+        if let Err(e) = progress.save() {
+            eprintln!("Could not save progress: {}", e);
+        }
     }
-    if player.v.y > PLAYER_MAX_SPEED {
-        player.v.y = PLAYER_MAX_SPEED;
-    } else if player.v.y < -PLAYER_MAX_SPEED {
-        player.v.y = -PLAYER_MAX_SPEED;
+}
+
+fn add_high_score(high_scores: &mut Vec<u32>, score: u32) {
+    high_scores.push(score);
+    high_scores.sort_unstable_by(|a, b| b.cmp(a));
+    high_scores.truncate(MAX_HIGH_SCORES);
+}
+
+// The recording is finished, write it out
+fn save_recording(recording: &mut Option<(PathBuf, Replay)>) {
+    if let Some((path, replay)) = recording.take() {
+        match replay.save(&path) {
+            Ok(()) => eprintln!("Recorded {} frames to {}", replay.frames(), path.display()),
+            Err(e) => eprintln!("Could not save the recording: {}", e),
+        }
     }
 }
 
 fn main() -> Result<(), String> {
+    let options = cli::Options::parse(env::args().skip(1))?;
+    if options.help {
+        print!("{}", cli::USAGE);
+        return Ok(());
+    }
+    let mut replay = match &options.replay {
+        Some(path) => Some(Replay::load(path)?),
+        None => None,
+    };
+
+    let mut settings = Settings::load();
+    // kept for the next run only if the options are saved
+    settings.scale = options.scale.unwrap_or(settings.scale);
+    settings.mode = options.mode.unwrap_or(settings.mode);
+
+    // without a window, SDL still needs a video driver to load the sprites
+    if options.headless {
+        sdl2::hint::set("SDL_VIDEODRIVER", "dummy");
+    }
     let sdl_context = sdl2::init().unwrap();
     let video = sdl_context.video().unwrap();
+    let controller_subsystem = sdl_context.game_controller()?;
 
-    // set up the draw window, scaled up 2x from actual game resolution
+    // set up the draw window, scaled up from actual game resolution
     // for a chunkier retro effect
-    let window = video
-        .window("Sideways", WIDTH * 2, HEIGHT * 2)
-        .position_centered()
-        .build()
-        .unwrap();
-    let mut canvas = window.into_canvas().build().unwrap();
-    canvas.set_scale(2.0, 2.0).unwrap();
+    let mut window = video.window("Sideways", WIDTH * settings.scale, HEIGHT * settings.scale);
+    window.position_centered().resizable();
+    if options.headless {
+        window.hidden();
+    }
+    let window = window.build().unwrap();
+    let mut canvas = if options.headless {
+        window.into_canvas().software().build().unwrap()
+    } else {
+        window.into_canvas().present_vsync().build().unwrap()
+    };
+    // not every driver can do vsync; fall back to sleeping between frames
+    let vsync =
+        canvas.info().flags & sdl2::sys::SDL_RendererFlags::SDL_RENDERER_PRESENTVSYNC as u32 != 0;
+    let mut pacer = timing::FramePacer::new(vsync);
+    let mut show_debug_overlay = false;
+    let mut show_hitboxes = false;
+    let mut console = Console::new();
+    let text_input = video.text_input();
+    text_input.stop();
 
     let _image_context = sdl2::image::init(InitFlag::PNG)?;
     let texture_creator = canvas.texture_creator();
+    let mut display = Display::new(&texture_creator, &mut canvas, settings.scale)?;
+    if !options.headless {
+        if let Err(e) = display.set_mode(&mut canvas, settings.mode) {
+            eprintln!("Could not switch to {}: {}", settings.mode.label(), e);
+        }
+    }
+    display.set_scanlines(settings.scanlines);
+    let mut assets = Assets::new(
+        &texture_creator,
+        Assets::find_dir(options.assets_dir.clone()),
+    );
+    let mut audio = if options.headless {
+        Audio::null()
+    } else {
+        Audio::new(&sdl_context, &assets)
+    };
+    audio.set_sfx_volume(settings.sfx_volume);
+    audio.set_music_volume(settings.music_volume);
 
-    let mut rng = thread_rng();
+    let levels = Levels::load(&assets, LEVELS).unwrap_or_else(|e| {
+        eprintln!(
+            "Could not load the levels, playing just {}: {}",
+            DEFAULT_LEVEL, e
+        );
+        Levels::single(DEFAULT_LEVEL)
+    });
+    let mut progress = SaveData::load();
+    if let Some(name) = options.level.as_ref().filter(|x| levels.get(x).is_none()) {
+        return Err(format!("--level: no level called {}", name));
+    }
+    if let Some(r) = replay.as_ref().filter(|r| levels.get(&r.level).is_none()) {
+        return Err(format!("--replay: no level called {}", r.level));
+    }
+    let mut level = levels.first().name.clone();
+    if let Some(name) = replay.as_ref().map(|r| &r.level).or(options.level.as_ref()) {
+        level = name.clone();
+    }
+    let mut starfield = load_starfield(&assets, &level);
+    let mut rules = match &replay {
+        Some(r) => load_rules(&assets, r.difficulty, r.rank),
+        None => load_rules(&assets, settings.difficulty, settings.rank),
+    };
 
-    let mut starfield = starfield::Starfield::new();
+    for name in &["playership", "playershot", "enemy_projectile", "enemyship"] {
+        assets.load_sheet(name)?;
+    }
+    let assets = assets;
+    let mut particles = particles::ParticleSystem::new(assets.load_texture("particle.png")?);
+    let sprites = game::Sprites {
+        player_ship: assets.sheet("playership"),
+        player_shot: assets.sheet("playershot"),
+        enemy_ship: assets.sheet("enemyship"),
+        enemy_shot: assets.sheet("enemy_projectile"),
+    };
+    let mut last_reload_check = Instant::now();
+    // the rules files changed, and haven't been reloaded yet
+    let mut rules_changed = false;
+    // reused every frame, so reading the keyboard doesn't allocate
+    let mut pressed_keys = HashSet::new();
 
-    let player_ship = texture_creator.load_texture("assets/playership.png")?;
-    let player_shot = texture_creator.load_texture("assets/playershot.png")?;
-    let enemy_shot = texture_creator.load_texture("assets/enemy_projectile.png")?;
-    let enemy_ship = texture_creator.load_texture("assets/enemyship.png")?;
-    let mut player = player::Player::from_sprite(&player_ship);
+    let mut state = GameState::Title;
+    let mut game: Option<game::Game> = None;
+    // network games only
+    let mut link: Option<Link> = None;
+    let mut session: Option<rollback::Rollback> = None;
+    if let Some(role) = &options.role {
+        let welcome = net::Welcome {
+            seed: thread_rng().gen(),
+            difficulty: rules.difficulty,
+            rank: rules.rank,
+            level: level.clone(),
+            input_delay: options.input_delay,
+        };
+        link = Some(Link::open(role, welcome, options.conditions)?);
+        state = GameState::Connecting;
+    }
+    // the next game played is recorded to this file
+    let mut record_to = options.record.clone();
+    let mut recording: Option<(PathBuf, Replay)> = None;
+    // how far the replay has got
+    let mut replay_frame = 0;
+    // once cheats have been used, no game played afterwards counts towards
+    // unlocking anything, and neither does one that started as a replay.
+    // Skipping ahead to a locked level is cheating too.
+    let mut cheated = options.god
+        || options
+            .level
+            .as_ref()
+            .is_some_and(|x| !progress.is_unlocked(&levels, x));
+    let mut keep_progress = true;
+    // game updates run so far, for --frames
+    let mut frames = 0;
+    if options.starts_game() {
+        let (setup, god) = match &replay {
+            Some(r) => (r.setup, r.god),
+            None => {
+                let setup = game::Setup {
+                    seed: options.seed.unwrap_or_else(|| thread_rng().gen()),
+                    players: 1,
+                    weapon: progress.weapon(),
+                };
+                (setup, options.god)
+            }
+        };
+        let mut g = new_game(
+            sprites,
+            setup,
+            rules,
+            levels.music(&level),
+            &assets,
+            &mut audio,
+            &mut particles,
+        );
+        if god {
+            g.toggle_god();
+        }
+        if let Some(path) = record_to.take() {
+            let r = Replay::new(setup, rules.difficulty, rules.rank, god, &level);
+            recording = Some((path, r));
+        }
+        keep_progress = !cheated && replay.is_none();
+        game = Some(g);
+        state = GameState::Playing;
+    }
+    let mut high_scores: Vec<u32> = vec![];
 
-    let mut projectiles: Vec<Projectile> = Vec::with_capacity(128);
-    let mut last_shot: Option<Instant> = None;
+    let mut title_menu = Menu::new(
+        "SIDEWAYS",
+        &["1 PLAYER", "2 PLAYERS", "HIGH SCORES", "OPTIONS", "QUIT"],
+    );
+    let mut level_select = level_menu(&levels, &progress);
+    let mut pause_menu = Menu::new("PAUSED", &["RESUME", "OPTIONS", "QUIT TO TITLE"]);
+    let mut options_menu = Menu::new(
+        "OPTIONS",
+        &[
+            &display_label(display.mode()),
+            &scale_label(display.scale()),
+            &scanlines_label(display.scanlines()),
+            &volume_label("SOUND", audio.sfx_volume()),
+            &volume_label("MUSIC", audio.music_volume()),
+            &difficulty_label(rules.difficulty),
+            &rank_label(rules.rank),
+            "BACK",
+        ],
+    );
 
-    let mut enemies: Vec<enemy::Enemy> = vec![];
-    // check once every second whether to spawn new enemy
-    let mut enemy_tick = Instant::now();
-    let one_second = Duration::from_secs(1);
+    // keep opened gamepads alive so they keep sending events
+    let mut controllers: Vec<GameController> = vec![];
 
     let mut event_pump = sdl_context.event_pump().unwrap();
 
     'running: loop {
+        let frame_allocations = alloc_count::allocations();
+        // headless runs go as fast as they can
+        let updates = if options.headless {
+            1
+        } else {
+            pacer.begin_frame()
+        };
         // Blank the window
         canvas.set_draw_color(Color::RGB(0, 0, 0));
         canvas.clear();
         for event in event_pump.poll_iter() {
             match event {
-                Event::Quit { .. }
-                | Event::KeyDown {
-                    keycode: Some(Keycode::Escape),
+                Event::Quit { .. } => break 'running,
+                Event::ControllerDeviceAdded { which, .. } => {
+                    if let Ok(controller) = controller_subsystem.open(which) {
+                        controllers.push(controller);
+                    }
+                }
+                Event::ControllerDeviceRemoved { which, .. } => {
+                    controllers.retain(|x| x.instance_id() != which);
+                }
+                Event::KeyDown {
+                    keycode: Some(Keycode::F11),
+                    repeat: false,
+                    ..
+                } => {
+                    display.toggle_fullscreen(&mut canvas)?;
+                    options_menu.set_item(0, display_label(display.mode()));
+                    save_settings(&mut settings, &display, &audio, &rules);
+                }
+                Event::KeyDown {
+                    keycode: Some(Keycode::F3),
+                    repeat: false,
+                    ..
+                } => show_debug_overlay = !show_debug_overlay,
+                Event::KeyDown {
+                    keycode: Some(Keycode::F4),
+                    repeat: false,
                     ..
-                } => break 'running,
+                } => show_hitboxes = !show_hitboxes,
+                // pause, frame step and slow motion for inspecting collisions,
+                // which the other player's game can't follow
+                Event::KeyDown {
+                    keycode: Some(key),
+                    repeat: false,
+                    ..
+                } if cfg!(debug_assertions) && DEBUG_TIME_KEYS.contains(&key) && link.is_none() => {
+                    cheated = true;
+                    keep_progress = false;
+                    match key {
+                        Keycode::F5 => pacer.toggle_frozen(),
+                        Keycode::F6 => pacer.step(),
+                        Keycode::F7 => pacer.set_time_scale(pacer.time_scale() / 2.0),
+                        _ => pacer.set_time_scale(pacer.time_scale() * 2.0),
+                    }
+                }
+                // the other player's game wouldn't know about the commands,
+                // and neither would a replay
+                Event::KeyDown {
+                    keycode: Some(Keycode::Backquote),
+                    repeat: false,
+                    ..
+                } if link.is_none() && recording.is_none() && replay.is_none() => {
+                    console.toggle();
+                    if console.is_open() {
+                        text_input.start();
+                    } else {
+                        text_input.stop();
+                    }
+                    continue;
+                }
                 _ => {}
             }
+            if console.is_open() {
+                let line = console.handle_event(&event);
+                if !console.is_open() {
+                    text_input.stop();
+                }
+                let command = match line.map(|x| Command::parse(&x)) {
+                    Some(Ok(command)) => command,
+                    Some(Err(e)) => {
+                        console.print(&e);
+                        continue;
+                    }
+                    None => continue,
+                };
+                // anything but looking something up is cheating
+                if !matches!(command, Command::Help | Command::Seed(None)) {
+                    cheated = true;
+                    keep_progress = false;
+                }
+                match command {
+                    Command::SpawnEnemy { kind, x, y } => {
+                        match (game.as_mut(), enemy::Kind::from_name(&kind)) {
+                            (_, None) => {
+                                let kinds: Vec<&str> =
+                                    enemy::Kind::ALL.iter().map(|x| x.name()).collect();
+                                console.print(&format!(
+                                    "unknown enemy {}. types: {}",
+                                    kind,
+                                    kinds.join(" ")
+                                ))
+                            }
+                            (Some(g), Some(kind)) => g.spawn_enemy(kind, x, y),
+                            (None, _) => console.print("no game running"),
+                        }
+                    }
+                    Command::God => match game.as_mut() {
+                        Some(g) => console.print(if g.toggle_god() {
+                            "god mode on"
+                        } else {
+                            "god mode off"
+                        }),
+                        None => console.print("no game running"),
+                    },
+                    Command::GiveWeapon(n) => match game.as_mut() {
+                        Some(g) if (1..=game::MAX_WEAPON).contains(&n) => g.give_weapon(n, &audio),
+                        Some(_) => {
+                            console.print(&format!("weapons go from 1 to {}", game::MAX_WEAPON))
+                        }
+                        None => console.print("no game running"),
+                    },
+                    Command::TimeScale(scale) => pacer.set_time_scale(scale),
+                    Command::Seed(None) => match game.as_ref() {
+                        Some(g) => console.print(&format!("seed {}", g.seed())),
+                        None => console.print("no game running"),
+                    },
+                    Command::Seed(Some(seed)) => {
+                        let players = game.as_ref().map_or(1, |g| g.players());
+                        game = Some(new_game(
+                            sprites,
+                            game::Setup {
+                                seed,
+                                players,
+                                weapon: progress.weapon(),
+                            },
+                            rules,
+                            levels.music(&level),
+                            &assets,
+                            &mut audio,
+                            &mut particles,
+                        ));
+                        state = GameState::Playing;
+                    }
+                    Command::Level(name) => {
+                        match starfield::Starfield::load(&assets, &starfield_path(&name)) {
+                            Ok(loaded) => {
+                                starfield = loaded;
+                                level = name;
+                                if let Some(g) = game.as_ref() {
+                                    let setup = game::Setup {
+                                        seed: thread_rng().gen(),
+                                        players: g.players(),
+                                        weapon: progress.weapon(),
+                                    };
+                                    game = Some(new_game(
+                                        sprites,
+                                        setup,
+                                        rules,
+                                        levels.music(&level),
+                                        &assets,
+                                        &mut audio,
+                                        &mut particles,
+                                    ));
+                                    state = GameState::Playing;
+                                }
+                            }
+                            Err(e) => console.print(&e),
+                        }
+                    }
+                    Command::Help => {
+                        for line in console::HELP {
+                            console.print(line);
+                        }
+                    }
+                }
+                continue;
+            }
+            let action = match MenuAction::from_event(&event) {
+                Some(action) => action,
+                None => continue,
+            };
+            if state != GameState::Playing {
+                match action {
+                    MenuAction::Up | MenuAction::Down => audio.play(Sound::MenuMove),
+                    MenuAction::Select => audio.play(Sound::MenuSelect),
+                    _ => {}
+                }
+            }
+            match state {
+                GameState::Title => match title_menu.navigate(action) {
+                    Some(Choice::Select(i)) if i < input::MAX_PLAYERS => {
+                        level_select = level_menu(&levels, &progress);
+                        state = GameState::LevelSelect { players: i + 1 };
+                    }
+                    Some(Choice::Select(2)) => state = GameState::HighScores,
+                    Some(Choice::Select(3)) => {
+                        options_menu.reset();
+                        state = GameState::Options { from_pause: false };
+                    }
+                    Some(Choice::Select(4)) => break 'running,
+                    _ => {}
+                },
+                GameState::LevelSelect { players } => match level_select.navigate(action) {
+                    Some(Choice::Select(i)) if i < levels.iter().count() => {
+                        let chosen = levels.iter().nth(i).unwrap();
+                        if progress.is_unlocked(&levels, &chosen.name) {
+                            level = chosen.name.clone();
+                            starfield = load_starfield(&assets, &level);
+                            let setup = game::Setup {
+                                seed: thread_rng().gen(),
+                                players,
+                                weapon: progress.weapon(),
+                            };
+                            if let Some(path) = record_to.take() {
+                                let r =
+                                    Replay::new(setup, rules.difficulty, rules.rank, false, &level);
+                                recording = Some((path, r));
+                            }
+                            keep_progress = !cheated;
+                            game = Some(new_game(
+                                sprites,
+                                setup,
+                                rules,
+                                levels.music(&level),
+                                &assets,
+                                &mut audio,
+                                &mut particles,
+                            ));
+                            state = GameState::Playing;
+                        }
+                    }
+                    Some(Choice::Select(_)) | Some(Choice::Back) => state = GameState::Title,
+                    _ => {}
+                },
+                GameState::Connecting => {
+                    if action == MenuAction::Back {
+                        link = None;
+                        title_menu.reset();
+                        state = GameState::Title;
+                    }
+                }
+                // a network game can't be paused
+                GameState::Playing => {
+                    if action == MenuAction::Back && link.is_none() {
+                        pause_menu.reset();
+                        audio.pause_music();
+                        state = GameState::Paused;
+                    }
+                }
+                GameState::Paused => match pause_menu.navigate(action) {
+                    Some(Choice::Select(0)) | Some(Choice::Back) => {
+                        audio.resume_music();
+                        state = GameState::Playing;
+                    }
+                    Some(Choice::Select(1)) => {
+                        options_menu.reset();
+                        state = GameState::Options { from_pause: true };
+                    }
+                    Some(Choice::Select(2)) => {
+                        if let Some(g) = game.take().filter(|_| keep_progress) {
+                            record_progress(&mut progress, &levels, &level, &g);
+                        }
+                        audio.stop_music();
+                        title_menu.reset();
+                        state = GameState::Title;
+                    }
+                    _ => {}
+                },
+                GameState::Options { from_pause } => match options_menu.navigate(action) {
+                    Some(Choice::Select(0)) => {
+                        display.set_mode(&mut canvas, display.mode().cycle(1))?;
+                        options_menu.set_item(0, display_label(display.mode()));
+                    }
+                    Some(Choice::Adjust(0, delta)) => {
+                        display.set_mode(&mut canvas, display.mode().cycle(delta))?;
+                        options_menu.set_item(0, display_label(display.mode()));
+                    }
+                    Some(Choice::Adjust(1, delta)) => {
+                        let scale = (display.scale() as i32 + delta).max(1) as u32;
+                        display.set_scale(&mut canvas, scale)?;
+                        options_menu.set_item(1, scale_label(display.scale()));
+                    }
+                    Some(Choice::Select(2)) | Some(Choice::Adjust(2, _)) => {
+                        display.set_scanlines(!display.scanlines());
+                        options_menu.set_item(2, scanlines_label(display.scanlines()));
+                    }
+                    Some(Choice::Adjust(3, delta)) => {
+                        audio.set_sfx_volume(adjust_volume(audio.sfx_volume(), delta));
+                        options_menu.set_item(3, volume_label("SOUND", audio.sfx_volume()));
+                        audio.play(Sound::Shoot);
+                    }
+                    Some(Choice::Adjust(4, delta)) => {
+                        audio.set_music_volume(adjust_volume(audio.music_volume(), delta));
+                        options_menu.set_item(4, volume_label("MUSIC", audio.music_volume()));
+                    }
+                    // changing the rules part way through would throw a
+                    // recording or replay out of step with the game
+                    Some(Choice::Select(5)) if recording.is_none() && replay.is_none() => {
+                        rules.difficulty = rules.difficulty.cycle(1);
+                        options_menu.set_item(5, difficulty_label(rules.difficulty));
+                    }
+                    Some(Choice::Adjust(5, delta)) if recording.is_none() && replay.is_none() => {
+                        rules.difficulty = rules.difficulty.cycle(delta);
+                        options_menu.set_item(5, difficulty_label(rules.difficulty));
+                    }
+                    Some(Choice::Select(6)) | Some(Choice::Adjust(6, _))
+                        if recording.is_none() && replay.is_none() =>
+                    {
+                        rules.rank = !rules.rank;
+                        options_menu.set_item(6, rank_label(rules.rank));
+                    }
+                    Some(Choice::Select(7)) | Some(Choice::Back) => {
+                        save_settings(&mut settings, &display, &audio, &rules);
+                        // a paused game carries on with the new difficulty
+                        if let Some(g) = game
+                            .as_mut()
+                            .filter(|_| recording.is_none() && replay.is_none())
+                        {
+                            g.set_rules(rules);
+                        }
+                        state = if from_pause {
+                            GameState::Paused
+                        } else {
+                            GameState::Title
+                        };
+                    }
+                    _ => {}
+                },
+                GameState::GameOver => {
+                    if action == MenuAction::Select || action == MenuAction::Back {
+                        if let Some(g) = game.take() {
+                            if keep_progress {
+                                record_progress(&mut progress, &levels, &level, &g);
+                            }
+                            for i in 0..g.players() {
+                                add_high_score(&mut high_scores, g.score(i));
+                            }
+                        }
+                        session = None;
+                        link = None;
+                        state = GameState::HighScores;
+                    }
+                }
+                GameState::HighScores => {
+                    if action == MenuAction::Select || action == MenuAction::Back {
+                        title_menu.reset();
+                        state = GameState::Title;
+                    }
+                }
+            }
         }
 
-        let pressed_keys: HashSet<_> = event_pump
-            .keyboard_state()
-            .pressed_scancodes()
-            .filter_map(Keycode::from_scancode)
-            .collect();
-
-        handle_input(
-            &pressed_keys,
-            &mut player,
-            &mut projectiles,
-            &player_shot,
-            &mut last_shot,
-        );
-        player.apply_velocity();
+        // the console takes the keyboard while it's open
+        pressed_keys.clear();
+        if !console.is_open() {
+            pressed_keys.extend(
+                event_pump
+                    .keyboard_state()
+                    .pressed_scancodes()
+                    .filter_map(Keycode::from_scancode),
+            );
+        }
+        // with two players each has their own keys, and gamepads go to
+        // players in the order they were plugged in
+        let mut inputs = [Input::default(); input::MAX_PLAYERS];
+        let players = game.as_ref().map_or(0, |g| g.players());
+        if !console.is_open() {
+            for (i, input) in inputs.iter_mut().enumerate().take(players) {
+                // in a network game each machine has one player
+                let controls = if players == 1 || link.is_some() {
+                    &settings.solo
+                } else if i == 0 {
+                    &settings.player1
+                } else {
+                    &settings.player2
+                };
+                *input = controls.read(&pressed_keys);
+                if let Some(controller) = controllers.get(i) {
+                    input.add_controller(controller);
+                }
+            }
+        }
+        for _ in 0..updates {
+            if state != GameState::Paused {
+                starfield.spawn_new_stars();
+                starfield.advance();
+                particles.advance();
+            }
+            let mut unknown_level = false;
+            if let Some(l) = link.as_mut() {
+                l.poll();
+                while let Some(packet) = l.receive() {
+                    if let Some(s) = session.as_mut() {
+                        s.receive(&packet);
+                    }
+                }
+                if state == GameState::Connecting {
+                    // the level name goes into file names, so it has to be
+                    // one of ours
+                    if let Some(welcome) = l.welcome().filter(|x| levels.get(&x.level).is_none()) {
+                        eprintln!(
+                            "The host is playing {}, which isn't in this game",
+                            welcome.level
+                        );
+                        unknown_level = true;
+                    } else if let Some(welcome) = l.welcome() {
+                        // the host's level, whether it's unlocked here or not
+                        if welcome.level != level {
+                            level = welcome.level.clone();
+                            starfield = load_starfield(&assets, &level);
+                        }
+                        let setup = game::Setup {
+                            seed: welcome.seed,
+                            players: input::MAX_PLAYERS,
+                            // both machines have to start the same, whatever
+                            // either player has unlocked
+                            weapon: 1,
+                        };
+                        let g = new_game(
+                            sprites,
+                            setup,
+                            load_rules(&assets, welcome.difficulty, welcome.rank),
+                            levels.music(&level),
+                            &assets,
+                            &mut audio,
+                            &mut particles,
+                        );
+                        let delay = welcome.input_delay;
+                        session = Some(rollback::Rollback::new(l.local_player(), delay, &g));
+                        game = Some(g);
+                        state = GameState::Playing;
+                    }
+                } else if state == GameState::Playing && l.timed_out() {
+                    eprintln!("Lost the connection to the other player");
+                    audio.stop_music();
+                    state = GameState::GameOver;
+                }
+            }
+            if unknown_level {
+                link = None;
+                title_menu.reset();
+                state = GameState::Title;
+            }
+            if state == GameState::Playing {
+                if let Some(g) = game.as_mut() {
+                    // once the replay runs out the players take over, unless
+                    // there's nobody there
+                    if let Some(r) = replay.as_ref() {
+                        match r.frame(replay_frame) {
+                            Some(recorded) => inputs[..players].copy_from_slice(recorded),
+                            None if options.headless => break 'running,
+                            None => replay = None,
+                        }
+                        replay_frame += 1;
+                    }
+                    if let Some((_, r)) = recording.as_mut() {
+                        r.push(&inputs);
+                        if r.is_full() {
+                            eprintln!("The recording is as long as it can be, stopping it");
+                            save_recording(&mut recording);
+                        }
+                    }
+                    match session.as_mut() {
+                        Some(s) => s.update(g, inputs[0], &audio, &mut particles),
+                        None => g.update(&inputs[..players], &audio, &mut particles),
+                    }
+                    // in a network game, only end once no late input from
+                    // the other player can change what happened
+                    let confirmed = session.as_ref().is_none_or(|s| s.is_confirmed());
+                    if g.is_over() && confirmed {
+                        audio.stop_music();
+                        state = GameState::GameOver;
+                    }
+                }
+            }
+            // keep sending after the game ends, in case the other player is
+            // still missing some of our inputs
+            if let (Some(l), Some(s)) = (link.as_mut(), session.as_ref()) {
+                l.send_inputs(&s.outgoing());
+            }
 
-        starfield.spawn_new_stars();
+            // a recording or replay is of one game, and ends with it
+            if game.as_ref().is_none_or(|g| g.is_over()) {
+                save_recording(&mut recording);
+                replay = None;
+            }
+            frames += 1;
+            if options.frames == Some(frames) || (options.headless && state == GameState::GameOver)
+            {
+                break 'running;
+            }
+        }
+        // nobody's watching, so don't draw or wait for the next frame
+        if options.headless {
+            continue;
+        }
 
         starfield.draw(&mut canvas)?;
-        starfield.advance();
-
-        let now = Instant::now();
-        if now - enemy_tick >= one_second && rng.gen::<f64>() < ENEMY_SPAWN_CHANCE {
-            enemies.push(enemy::Enemy::from_sprite(&enemy_ship));
-            enemy_tick = now;
+        // keep drawing the game behind the pause and game over screens
+        if let Some(g) = game.as_ref() {
+            g.draw(&mut canvas)?;
+        }
+        particles.draw(&mut canvas)?;
+        if let Some(g) = game.as_ref() {
+            if show_hitboxes {
+                g.draw_debug(&mut canvas)?;
+            }
+            g.draw_hud(&mut canvas)?;
         }
 
-        for enemy in &mut enemies {
-            let mut hit_by_proj_idx: Option<usize> = None;
-            for (i, proj) in projectiles.iter_mut().enumerate() {
-                if !proj.lethal_to_enemy() {
-                    continue;
-                }
-                if enemy.collide(&proj.shape) {
-                    enemy.die();
-                    hit_by_proj_idx = Some(i);
-                    break;
+        let white = Color::RGB(0xff, 0xff, 0xff);
+        match state {
+            GameState::Title => title_menu.draw(&mut canvas)?,
+            GameState::LevelSelect { .. } => level_select.draw(&mut canvas)?,
+            GameState::Connecting => {
+                let status = link.as_ref().map(|l| l.status()).unwrap_or_default();
+                text::draw_text_centered(&mut canvas, "NETWORK GAME", HEIGHT as i32 / 3, 4, white)?;
+                text::draw_text_centered(&mut canvas, &status, HEIGHT as i32 / 2, 2, white)?;
+                text::draw_text_centered(
+                    &mut canvas,
+                    "ESCAPE TO CANCEL",
+                    HEIGHT as i32 * 2 / 3,
+                    1,
+                    white,
+                )?;
+            }
+            GameState::Paused => pause_menu.draw(&mut canvas)?,
+            GameState::Options { .. } => options_menu.draw(&mut canvas)?,
+            GameState::GameOver => {
+                text::draw_text_centered(&mut canvas, "GAME OVER", HEIGHT as i32 / 3, 4, white)?;
+                if let Some(g) = game.as_ref() {
+                    let score = if g.players() == 1 {
+                        format!("SCORE {}", g.score(0))
+                    } else {
+                        (0..g.players())
+                            .map(|i| format!("{}P {}", i + 1, g.score(i)))
+                            .collect::<Vec<_>>()
+                            .join("  ")
+                    };
+                    text::draw_text_centered(&mut canvas, &score, HEIGHT as i32 / 2, 2, white)?;
                 }
+                text::draw_text_centered(
+                    &mut canvas,
+                    "PRESS ENTER",
+                    HEIGHT as i32 * 2 / 3,
+                    1,
+                    white,
+                )?;
             }
-            if let Some(i) = hit_by_proj_idx {
-                projectiles.remove(i);
-                continue;
+            GameState::HighScores => {
+                text::draw_text_centered(&mut canvas, "HIGH SCORES", HEIGHT as i32 / 4, 4, white)?;
+                for i in 0..MAX_HIGH_SCORES {
+                    let line = match high_scores.get(i) {
+                        Some(score) => format!("{}. {:06}", i + 1, score),
+                        None => format!("{}. ------", i + 1),
+                    };
+                    text::draw_text_centered(
+                        &mut canvas,
+                        &line,
+                        HEIGHT as i32 / 2 + i as i32 * 20,
+                        2,
+                        white,
+                    )?;
+                }
             }
-            if !enemy.is_alive() || !enemy.is_in_screen() {
-                continue;
+            GameState::Playing => {}
+        }
+
+        if show_debug_overlay {
+            let mut counts = game.as_ref().map(|g| g.counts()).unwrap_or_default();
+            counts.particles = particles.count();
+            counts.allocations = alloc_count::allocations() - frame_allocations;
+            counts.rollback = session.as_ref().map(|s| s.resimulated());
+            debug::draw_overlay(&mut canvas, &pacer.stats(), pacer.vsync(), &counts)?;
+        }
+        if cfg!(debug_assertions) {
+            debug::draw_time_status(&mut canvas, pacer.is_frozen(), pacer.time_scale())?;
+        }
+        if console.is_open() {
+            console.draw(&mut canvas)?;
+        }
+
+        display.draw_scanlines(&mut canvas)?;
+        canvas.present();
+
+        if cfg!(debug_assertions) && last_reload_check.elapsed() >= HOT_RELOAD_INTERVAL {
+            last_reload_check = Instant::now();
+            for name in assets.hot_reload() {
+                if starfield.uses(&name) {
+                    starfield = load_starfield(&assets, &level);
+                } else if name == COLLISION_MATRIX || name == PLAYER_HANDLING {
+                    rules_changed = true;
+                } else if name == "particle.png" {
+                    match assets.load_texture(&name) {
+                        Ok(texture) => particles.replace_sprite(texture),
+                        Err(e) => eprintln!("Could not reload {}: {}", name, e),
+                    }
+                }
             }
-            if enemy.last_shot.is_none()
-                || (enemy.last_shot.is_some()
-                    && now - enemy.last_shot.unwrap() >= Duration::from_millis(enemy.shoot_freq))
-            {
-                let player_center_x =
-                    player.shape.get_box().x + (player.shape.get_box().width as i32) / 2;
-                let player_center_y =
-                    player.shape.get_box().y + (player.shape.get_box().height as i32) / 2;
-                projectiles.push(Projectile::from_sprite(
-                    &enemy_shot,
-                    &enemy.shape,
-                    ENEMY_PROJECTILE_SPEED,
-                    ProjShape::Circle,
-                    LethalTo::Player,
-                    Some((player_center_x, player_center_y)),
-                ));
-                enemy.last_shot = Some(now);
+            // changing the rules part way through would throw a recording,
+            // a replay or the other player's game out of step, so that waits
+            // until they're over
+            if rules_changed && link.is_none() && recording.is_none() && replay.is_none() {
+                rules_changed = false;
+                rules = load_rules(&assets, rules.difficulty, rules.rank);
+                if let Some(g) = game.as_mut() {
+                    g.set_rules(rules);
+                }
             }
-            enemy.draw(&mut canvas)?;
-            enemy.advance();
         }
+        pacer.end_frame();
+    }
 
-        player.draw(&mut canvas)?;
-        for proj in &mut projectiles {
-            proj.draw(&mut canvas)?;
-            proj.advance();
+    save_recording(&mut recording);
+    if options.headless {
+        println!("frames {}", frames);
+        if let Some(g) = game.as_ref() {
+            for i in 0..g.players() {
+                println!("player {} score {} lives {}", i + 1, g.score(i), g.lives(i));
+            }
         }
-        canvas.present();
-        enemies.retain(|x| x.is_alive() && x.is_in_screen());
-        projectiles.retain(|x| x.is_in_screen());
-        ::std::thread::sleep(Duration::new(0, 1_000_000_000u32 / 50));
     }
     Ok(())
 }
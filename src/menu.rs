@@ -0,0 +1,130 @@
+use crate::text;
+use sdl2::controller::Button;
+use sdl2::event::Event;
+use sdl2::keyboard::Keycode;
+use sdl2::pixels::Color;
+use sdl2::render::Canvas;
+use sdl2::video::Window;
+
+const TITLE_SCALE: u32 = 4;
+const ITEM_SCALE: u32 = 2;
+const ITEM_SPACING: i32 = 24;
+
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum MenuAction {
+    Up,
+    Down,
+    Left,
+    Right,
+    Select,
+    Back,
+}
+
+impl MenuAction {
+    // Map keyboard and gamepad events to menu navigation
+    pub fn from_event(event: &Event) -> Option<MenuAction> {
+        match event {
+            Event::KeyDown {
+                keycode: Some(k),
+                repeat: false,
+                ..
+            } => match k {
+                Keycode::Up => Some(MenuAction::Up),
+                Keycode::Down => Some(MenuAction::Down),
+                Keycode::Left => Some(MenuAction::Left),
+                Keycode::Right => Some(MenuAction::Right),
+                Keycode::Return | Keycode::KpEnter | Keycode::Space => Some(MenuAction::Select),
+                Keycode::Escape | Keycode::Backspace => Some(MenuAction::Back),
+                _ => None,
+            },
+            Event::ControllerButtonDown { button, .. } => match button {
+                Button::DPadUp => Some(MenuAction::Up),
+                Button::DPadDown => Some(MenuAction::Down),
+                Button::DPadLeft => Some(MenuAction::Left),
+                Button::DPadRight => Some(MenuAction::Right),
+                Button::A => Some(MenuAction::Select),
+                Button::B | Button::Back | Button::Start => Some(MenuAction::Back),
+                _ => None,
+            },
+            _ => None,
+        }
+    }
+}
+
+// What the player did with a menu
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum Choice {
+    Select(usize),
+    // Left/Right on an item, e.g. to toggle an option
+    Adjust(usize, i32),
+    Back,
+}
+
+pub struct Menu {
+    title: String,
+    items: Vec<String>,
+    selected: usize,
+}
+
+impl Menu {
+    pub fn new(title: &str, items: &[&str]) -> Self {
+        Menu {
+            title: title.to_string(),
+            items: items.iter().map(|x| x.to_string()).collect(),
+            selected: 0,
+        }
+    }
+
+    pub fn set_item(&mut self, i: usize, label: String) {
+        self.items[i] = label;
+    }
+
+    pub fn reset(&mut self) {
+        self.selected = 0;
+    }
+
+    pub fn navigate(&mut self, action: MenuAction) -> Option<Choice> {
+        let count = self.items.len();
+        match action {
+            MenuAction::Up => {
+                self.selected = (self.selected + count - 1) % count;
+                None
+            }
+            MenuAction::Down => {
+                self.selected = (self.selected + 1) % count;
+                None
+            }
+            MenuAction::Left => Some(Choice::Adjust(self.selected, -1)),
+            MenuAction::Right => Some(Choice::Adjust(self.selected, 1)),
+            MenuAction::Select => Some(Choice::Select(self.selected)),
+            MenuAction::Back => Some(Choice::Back),
+        }
+    }
+
+    pub fn draw(&self, canvas: &mut Canvas<Window>) -> Result<(), String> {
+        let title_y = crate::HEIGHT as i32 / 4;
+        text::draw_text_centered(
+            canvas,
+            &self.title,
+            title_y,
+            TITLE_SCALE,
+            Color::RGB(0xff, 0xff, 0xff),
+        )?;
+        let items_y = title_y + (text::GLYPH_HEIGHT * TITLE_SCALE) as i32 + ITEM_SPACING * 2;
+        for (i, item) in self.items.iter().enumerate() {
+            let (label, color) = if i == self.selected {
+                (format!("> {} <", item), Color::RGB(0xff, 0xd8, 0x40))
+            } else {
+                (item.clone(), Color::RGB(0xca, 0xca, 0xca))
+            };
+            text::draw_text_centered(
+                canvas,
+                &label,
+                items_y + i as i32 * ITEM_SPACING,
+                ITEM_SCALE,
+                color,
+            )?;
+        }
+        Ok(())
+    }
+}
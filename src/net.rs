@@ -0,0 +1,428 @@
+// Two-player games over UDP. One player hosts and waits on a port, the
+// other joins by address. Once they've found each other the host sends the
+// seed, difficulty, level and input delay, so both start the same game, and
+// from then on they only send each other inputs (see rollback.rs).
+//
+// Every packet starts with MAGIC and a message type:
+//   hello    the joining player looking for the host
+//   welcome  seed (u64), difficulty (u8), rank (u8), input delay (u8),
+//            level name length (u8), level name
+//   inputs   ack (u32), first frame (u32), count (u8), one byte per input
+// Numbers are little endian.
+
+use crate::difficulty::Difficulty;
+use crate::input::Input;
+use crate::rollback::MAX_INPUT_DELAY;
+use rand::prelude::*;
+use rand::rngs::StdRng;
+use std::collections::VecDeque;
+use std::convert::TryInto;
+use std::net::{SocketAddr, ToSocketAddrs, UdpSocket};
+use std::time::{Duration, Instant};
+
+const MAGIC: &[u8; 4] = b"SWY1";
+const HELLO: u8 = 0;
+const WELCOME: u8 = 1;
+const INPUTS: u8 = 2;
+// Inputs per packet. Every packet repeats all the inputs the other side
+// hasn't acknowledged yet, up to this many, so lost ones arrive with the next.
+pub const MAX_PACKET_INPUTS: usize = 32;
+// Longest level name that fits in a welcome
+const MAX_LEVEL_NAME: usize = 32;
+// The longest packet is a welcome with the longest level name
+const MAX_PACKET: usize = 17 + MAX_LEVEL_NAME;
+const _: () = assert!(14 + MAX_PACKET_INPUTS <= MAX_PACKET);
+// Give up on the other player after hearing nothing from them for this long
+const TIMEOUT: Duration = Duration::from_secs(5);
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Role {
+    // wait for the other player on this port
+    Host(u16),
+    // address of the host, like 127.0.0.1:7000
+    Join(String),
+}
+
+// Bad network conditions to test with, applied to the packets we send
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Conditions {
+    // added to every packet, one way
+    pub latency: Duration,
+    // chance of dropping a packet, from 0.0 to 1.0
+    pub loss: f64,
+}
+
+// What the host tells the other player, so they start the same game
+#[derive(Debug, Clone, PartialEq)]
+pub struct Welcome {
+    pub seed: u64,
+    pub difficulty: Difficulty,
+    pub rank: bool,
+    pub level: String,
+    // both players' input is held back this many frames, the host's choice
+    pub input_delay: u32,
+}
+
+// A run of one player's inputs, for consecutive frames
+#[derive(Debug, Clone, Copy)]
+pub struct InputPacket {
+    // the sender has our inputs for every frame before this one
+    pub ack: u32,
+    pub first: u32,
+    len: usize,
+    inputs: [Input; MAX_PACKET_INPUTS],
+}
+
+impl InputPacket {
+    // Anything past MAX_PACKET_INPUTS is left for a later packet
+    pub fn new(ack: u32, first: u32, inputs: impl Iterator<Item = Input>) -> Self {
+        let mut packet = InputPacket {
+            ack,
+            first,
+            len: 0,
+            inputs: [Input::default(); MAX_PACKET_INPUTS],
+        };
+        for (slot, input) in packet.inputs.iter_mut().zip(inputs) {
+            *slot = input;
+            packet.len += 1;
+        }
+        packet
+    }
+
+    pub fn inputs(&self) -> &[Input] {
+        &self.inputs[..self.len]
+    }
+}
+
+enum Message {
+    Hello,
+    Welcome(Welcome),
+    Inputs(InputPacket),
+}
+
+impl Message {
+    // Returns the length of the packet written to buffer
+    fn encode(&self, buffer: &mut [u8; MAX_PACKET]) -> usize {
+        buffer[..4].copy_from_slice(MAGIC);
+        match self {
+            Message::Hello => {
+                buffer[4] = HELLO;
+                5
+            }
+            Message::Welcome(welcome) => {
+                buffer[4] = WELCOME;
+                buffer[5..13].copy_from_slice(&welcome.seed.to_le_bytes());
+                buffer[13] = Difficulty::ALL
+                    .iter()
+                    .position(|&x| x == welcome.difficulty)
+                    .unwrap() as u8;
+                buffer[14] = welcome.rank as u8;
+                buffer[15] = welcome.input_delay as u8;
+                let level = welcome.level.as_bytes();
+                buffer[16] = level.len() as u8;
+                buffer[17..17 + level.len()].copy_from_slice(level);
+                17 + level.len()
+            }
+            Message::Inputs(packet) => {
+                buffer[4] = INPUTS;
+                buffer[5..9].copy_from_slice(&packet.ack.to_le_bytes());
+                buffer[9..13].copy_from_slice(&packet.first.to_le_bytes());
+                buffer[13] = packet.len as u8;
+                for (byte, input) in buffer[14..].iter_mut().zip(packet.inputs()) {
+                    *byte = input.to_bits();
+                }
+                14 + packet.len
+            }
+        }
+    }
+
+    // None for anything that isn't one of ours, or is cut short
+    fn decode(bytes: &[u8]) -> Option<Message> {
+        if bytes.len() < 5 || &bytes[..4] != MAGIC {
+            return None;
+        }
+        let u32_at = |i: usize| Some(u32::from_le_bytes(bytes.get(i..i + 4)?.try_into().ok()?));
+        match bytes[4] {
+            HELLO => Some(Message::Hello),
+            WELCOME if bytes.len() >= 17 => {
+                let input_delay = u32::from(bytes[15]);
+                if input_delay > MAX_INPUT_DELAY {
+                    return None;
+                }
+                let level = bytes.get(17..17 + bytes[16] as usize)?;
+                Some(Message::Welcome(Welcome {
+                    seed: u64::from_le_bytes(bytes[5..13].try_into().ok()?),
+                    difficulty: *Difficulty::ALL.get(bytes[13] as usize)?,
+                    rank: bytes[14] != 0,
+                    level: String::from_utf8(level.to_vec()).ok()?,
+                    input_delay,
+                }))
+            }
+            INPUTS if bytes.len() >= 14 => {
+                let len = bytes[13] as usize;
+                if len > MAX_PACKET_INPUTS {
+                    return None;
+                }
+                let inputs = bytes.get(14..14 + len)?;
+                Some(Message::Inputs(InputPacket::new(
+                    u32_at(5)?,
+                    u32_at(9)?,
+                    inputs.iter().map(|&x| Input::from_bits(x)),
+                )))
+            }
+            _ => None,
+        }
+    }
+}
+
+// The connection to the other player
+pub struct Link {
+    socket: UdpSocket,
+    hosting: bool,
+    // the host doesn't know where the other player is until they say hello
+    peer: Option<SocketAddr>,
+    // the host has this from the start, the other player once it arrives
+    welcome: Option<Welcome>,
+    conditions: Conditions,
+    rng: StdRng,
+    // packets held back to fake latency, and when to send them
+    delayed: VecDeque<(Instant, Vec<u8>)>,
+    buffer: [u8; MAX_PACKET],
+    last_heard: Instant,
+}
+
+impl Link {
+    // Start listening as the host, or start looking for one. welcome is what
+    // the host sends, and is ignored when joining.
+    pub fn open(role: &Role, welcome: Welcome, conditions: Conditions) -> Result<Link, String> {
+        if welcome.level.len() > MAX_LEVEL_NAME {
+            return Err(format!(
+                "level names in network games go up to {} letters",
+                MAX_LEVEL_NAME
+            ));
+        }
+        let (socket, peer) = match role {
+            Role::Host(port) => (UdpSocket::bind(("0.0.0.0", *port)), None),
+            Role::Join(address) => {
+                let peer = address
+                    .to_socket_addrs()
+                    .map_err(|e| format!("{}: {}", address, e))?
+                    .next()
+                    .ok_or_else(|| format!("{}: no such address", address))?;
+                (UdpSocket::bind(("0.0.0.0", 0)), Some(peer))
+            }
+        };
+        let socket = socket.map_err(|e| e.to_string())?;
+        socket.set_nonblocking(true).map_err(|e| e.to_string())?;
+        let hosting = peer.is_none();
+        Ok(Link {
+            socket,
+            hosting,
+            peer,
+            welcome: if hosting { Some(welcome) } else { None },
+            conditions,
+            rng: StdRng::from_entropy(),
+            delayed: VecDeque::new(),
+            buffer: [0; MAX_PACKET],
+            last_heard: Instant::now(),
+        })
+    }
+
+    // The host is player 1 and the other player is player 2
+    pub fn local_player(&self) -> usize {
+        if self.hosting {
+            0
+        } else {
+            1
+        }
+    }
+
+    // What to show while waiting for the other player
+    pub fn status(&self) -> String {
+        match (self.hosting, self.socket.local_addr(), self.peer) {
+            (true, Ok(address), _) => format!("WAITING ON PORT {}", address.port()),
+            (false, _, Some(peer)) => format!("CONNECTING TO {}", peer),
+            _ => "CONNECTING".to_string(),
+        }
+    }
+
+    // The game to start, once both players have found each other
+    pub fn welcome(&self) -> Option<&Welcome> {
+        self.peer.and(self.welcome.as_ref())
+    }
+
+    pub fn timed_out(&self) -> bool {
+        self.welcome().is_some() && self.last_heard.elapsed() > TIMEOUT
+    }
+
+    // Send the packets whose fake latency is up, and keep saying hello
+    // until the host answers. Called once per update.
+    pub fn poll(&mut self) {
+        let now = Instant::now();
+        while self.delayed.front().is_some_and(|x| x.0 <= now) {
+            if let (Some((_, packet)), Some(peer)) = (self.delayed.pop_front(), self.peer) {
+                // nobody listening yet is fine, we'll send again
+                let _ = self.socket.send_to(&packet, peer);
+            }
+        }
+        if self.welcome().is_none() && !self.hosting {
+            self.send(&Message::Hello);
+        }
+    }
+
+    pub fn send_inputs(&mut self, packet: &InputPacket) {
+        if self.welcome().is_some() {
+            self.send(&Message::Inputs(*packet));
+        }
+    }
+
+    // The next inputs from the other player, answering any handshake
+    // packets on the way
+    pub fn receive(&mut self) -> Option<InputPacket> {
+        loop {
+            let (len, from) = match self.socket.recv_from(&mut self.buffer) {
+                Ok(received) => received,
+                // nothing waiting, or the other side isn't up yet
+                Err(_) => return None,
+            };
+            if self.peer.is_some() && self.peer != Some(from) {
+                continue;
+            }
+            match Message::decode(&self.buffer[..len]) {
+                // the first hello decides who we're playing with. Answer
+                // every one, in case a welcome got lost.
+                Some(Message::Hello) if self.hosting => {
+                    self.peer = Some(from);
+                    self.last_heard = Instant::now();
+                    if let Some(welcome) = self.welcome.clone() {
+                        self.send(&Message::Welcome(welcome));
+                    }
+                }
+                Some(Message::Welcome(welcome)) if !self.hosting => {
+                    self.welcome = Some(welcome);
+                    self.last_heard = Instant::now();
+                }
+                Some(Message::Inputs(packet)) if self.welcome().is_some() => {
+                    self.last_heard = Instant::now();
+                    return Some(packet);
+                }
+                _ => {}
+            }
+        }
+    }
+
+    fn send(&mut self, message: &Message) {
+        let peer = match self.peer {
+            Some(peer) => peer,
+            None => return,
+        };
+        if self.conditions.loss > 0.0 && self.rng.gen::<f64>() < self.conditions.loss {
+            return;
+        }
+        let mut packet = [0; MAX_PACKET];
+        let len = message.encode(&mut packet);
+        if self.conditions.latency > Duration::from_secs(0) {
+            let due = Instant::now() + self.conditions.latency;
+            self.delayed.push_back((due, packet[..len].to_vec()));
+        } else {
+            let _ = self.socket.send_to(&packet[..len], peer);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn round_trip(message: &Message) -> Option<Message> {
+        let mut buffer = [0; MAX_PACKET];
+        let len = message.encode(&mut buffer);
+        Message::decode(&buffer[..len])
+    }
+
+    fn welcome() -> Welcome {
+        Welcome {
+            seed: 0x0123_4567_89ab_cdef,
+            difficulty: Difficulty::Hard,
+            rank: true,
+            level: "x".repeat(MAX_LEVEL_NAME),
+            input_delay: MAX_INPUT_DELAY,
+        }
+    }
+
+    fn encoded_welcome() -> Vec<u8> {
+        let mut buffer = [0; MAX_PACKET];
+        let len = Message::Welcome(welcome()).encode(&mut buffer);
+        buffer[..len].to_vec()
+    }
+
+    #[test]
+    fn hello_round_trip() {
+        assert!(matches!(round_trip(&Message::Hello), Some(Message::Hello)));
+    }
+
+    #[test]
+    fn welcome_round_trip() {
+        match round_trip(&Message::Welcome(welcome())) {
+            Some(Message::Welcome(decoded)) => assert_eq!(decoded, welcome()),
+            _ => panic!("not a welcome"),
+        }
+    }
+
+    #[test]
+    fn inputs_round_trip() {
+        let inputs = (0..MAX_PACKET_INPUTS).map(|i| Input::from_bits(i as u8));
+        let packet = InputPacket::new(7, 1_000_000, inputs);
+        match round_trip(&Message::Inputs(packet)) {
+            Some(Message::Inputs(decoded)) => {
+                assert_eq!(decoded.ack, 7);
+                assert_eq!(decoded.first, 1_000_000);
+                assert_eq!(decoded.inputs(), packet.inputs());
+            }
+            _ => panic!("not inputs"),
+        }
+    }
+
+    #[test]
+    fn packets_past_the_limit_are_cut() {
+        let inputs = (0..MAX_PACKET_INPUTS * 2).map(|_| Input::default());
+        assert_eq!(
+            InputPacket::new(0, 0, inputs).inputs().len(),
+            MAX_PACKET_INPUTS
+        );
+    }
+
+    #[test]
+    fn strangers_and_short_packets_are_ignored() {
+        let mut bytes = encoded_welcome();
+        assert!(Message::decode(&bytes[..bytes.len() - 1]).is_none());
+        assert!(Message::decode(&bytes[..10]).is_none());
+        bytes[0] = b'X';
+        assert!(Message::decode(&bytes).is_none());
+        assert!(Message::decode(b"SWY1\x09").is_none());
+    }
+
+    #[test]
+    fn bad_welcomes_are_ignored() {
+        let mut bytes = encoded_welcome();
+        bytes[13] = Difficulty::ALL.len() as u8;
+        assert!(Message::decode(&bytes).is_none());
+
+        let mut bytes = encoded_welcome();
+        bytes[15] = MAX_INPUT_DELAY as u8 + 1;
+        assert!(Message::decode(&bytes).is_none());
+
+        let mut bytes = encoded_welcome();
+        bytes[17] = 0xff;
+        assert!(Message::decode(&bytes).is_none());
+    }
+
+    #[test]
+    fn too_many_inputs_are_ignored() {
+        let mut bytes = vec![0; 14 + MAX_PACKET_INPUTS + 1];
+        bytes[..4].copy_from_slice(MAGIC);
+        bytes[4] = INPUTS;
+        bytes[13] = MAX_PACKET_INPUTS as u8 + 1;
+        assert!(Message::decode(&bytes).is_none());
+    }
+}
@@ -0,0 +1,247 @@
+use crate::pool::Pool;
+use crate::vec2::Vec2;
+use rand::prelude::*;
+use sdl2::pixels::Color;
+use sdl2::rect::Rect;
+use sdl2::render::{BlendMode, Canvas, Texture};
+use sdl2::video::Window;
+use std::f64::consts::PI;
+
+const MAX_PARTICLES: usize = 512;
+
+#[derive(Clone, Copy, Debug)]
+struct Particle {
+    position: Vec2,
+    v: Vec2,
+    drag: f64,
+    life: u32, // frames left
+    max_life: u32,
+    start_color: Color,
+    end_color: Color,
+    size: u32,
+    sprite: bool,
+}
+
+impl Particle {
+    // 0.0 when the particle is spawned, 1.0 when it dies
+    fn age(&self) -> f64 {
+        1.0 - self.life as f64 / self.max_life as f64
+    }
+
+    fn color(&self) -> Color {
+        let t = self.age();
+        let lerp = |a: u8, b: u8| (a as f64 + (b as f64 - a as f64) * t) as u8;
+        Color::RGBA(
+            lerp(self.start_color.r, self.end_color.r),
+            lerp(self.start_color.g, self.end_color.g),
+            lerp(self.start_color.b, self.end_color.b),
+            lerp(self.start_color.a, self.end_color.a),
+        )
+    }
+}
+
+// Describes a burst of particles
+#[derive(Clone, Copy, Debug)]
+pub struct Emitter {
+    pub count: usize,
+    pub direction: f64, // radians, 0.0 points right
+    pub spread: f64,    // radians to either side of direction, PI for all around
+    pub speed: (f64, f64),
+    pub life: (u32, u32), // frames
+    pub start_color: Color,
+    pub end_color: Color,
+    pub size: u32,
+    // draw with the particle sprite, shrinking over its lifetime,
+    // instead of as a square of pixels
+    pub sprite: bool,
+    pub drag: f64, // velocity multiplier per frame
+}
+
+impl Emitter {
+    pub fn explosion() -> Self {
+        Emitter {
+            count: 24,
+            direction: 0.0,
+            spread: PI,
+            speed: (0.5, 3.0),
+            life: (20, 40),
+            start_color: Color::RGBA(0xff, 0xe0, 0x60, 0xff),
+            end_color: Color::RGBA(0xa0, 0x10, 0x00, 0x00),
+            size: 6,
+            sprite: true,
+            drag: 0.92,
+        }
+    }
+
+    pub fn debris() -> Self {
+        Emitter {
+            count: 10,
+            direction: PI,
+            spread: PI / 2.0,
+            speed: (1.0, 4.0),
+            life: (30, 50),
+            start_color: Color::RGBA(0x90, 0x90, 0xa0, 0xff),
+            end_color: Color::RGBA(0x30, 0x30, 0x38, 0x00),
+            size: 2,
+            sprite: false,
+            drag: 0.98,
+        }
+    }
+
+    pub fn exhaust() -> Self {
+        Emitter {
+            count: 2,
+            direction: PI,
+            spread: PI / 10.0,
+            speed: (2.0, 4.0),
+            life: (6, 12),
+            start_color: Color::RGBA(0x80, 0xd0, 0xff, 0xff),
+            end_color: Color::RGBA(0x10, 0x20, 0x80, 0x00),
+            size: 1,
+            sprite: false,
+            drag: 0.9,
+        }
+    }
+
+    pub fn sparks() -> Self {
+        Emitter {
+            count: 12,
+            direction: 0.0,
+            spread: PI,
+            speed: (1.5, 5.0),
+            life: (8, 16),
+            start_color: Color::RGBA(0xff, 0xff, 0xc0, 0xff),
+            end_color: Color::RGBA(0xff, 0x80, 0x00, 0x00),
+            size: 1,
+            sprite: false,
+            drag: 0.85,
+        }
+    }
+
+    pub fn impact() -> Self {
+        Emitter {
+            count: 4,
+            direction: PI,
+            spread: PI / 3.0,
+            speed: (0.5, 2.0),
+            life: (4, 8),
+            start_color: Color::RGBA(0xff, 0xff, 0xff, 0xff),
+            end_color: Color::RGBA(0x60, 0xc0, 0xff, 0x00),
+            size: 3,
+            sprite: true,
+            drag: 0.8,
+        }
+    }
+
+    // Point the burst in a different direction
+    pub fn towards(mut self, direction: f64) -> Self {
+        self.direction = direction;
+        self
+    }
+}
+
+// Fixed pool of particles, so effects never allocate during the game.
+// When the pool is full new particles are simply dropped.
+pub struct ParticleSystem<'b> {
+    particles: Pool<Particle>,
+    sprite: Texture<'b>,
+    // off while frames are played again after a rollback, so their effects
+    // don't show up twice
+    emitting: bool,
+}
+
+impl<'b> ParticleSystem<'b> {
+    pub fn new(sprite: Texture<'b>) -> Self {
+        ParticleSystem {
+            particles: Pool::with_capacity(MAX_PARTICLES),
+            sprite,
+            emitting: true,
+        }
+    }
+
+    pub fn replace_sprite(&mut self, sprite: Texture<'b>) {
+        self.sprite = sprite;
+    }
+
+    pub fn clear(&mut self) {
+        self.particles.clear();
+    }
+
+    pub fn count(&self) -> usize {
+        self.particles.len()
+    }
+
+    pub fn set_emitting(&mut self, emitting: bool) {
+        self.emitting = emitting;
+    }
+
+    pub fn emit(&mut self, emitter: &Emitter, x: f64, y: f64) {
+        if !self.emitting {
+            return;
+        }
+        let mut rng = thread_rng();
+        for _ in 0..emitter.count {
+            if self.particles.is_full() {
+                return;
+            }
+            let angle = emitter.direction + rng.gen_range(-emitter.spread, emitter.spread);
+            let speed = rng.gen_range(emitter.speed.0, emitter.speed.1);
+            let life = rng.gen_range(emitter.life.0, emitter.life.1 + 1);
+            self.particles.insert(Particle {
+                position: Vec2::new(x, y),
+                v: Vec2::from_angle(angle) * speed,
+                drag: emitter.drag,
+                life,
+                max_life: life,
+                start_color: emitter.start_color,
+                end_color: emitter.end_color,
+                size: emitter.size,
+                sprite: emitter.sprite,
+            });
+        }
+    }
+
+    pub fn advance(&mut self) {
+        self.particles.retain(|p| {
+            if p.life == 0 {
+                return false;
+            }
+            p.life -= 1;
+            p.position += p.v;
+            p.v = p.v * p.drag;
+            true
+        });
+    }
+
+    pub fn draw(&mut self, canvas: &mut Canvas<Window>) -> Result<(), String> {
+        canvas.set_blend_mode(BlendMode::Blend);
+        for (_, p) in self.particles.iter() {
+            let color = p.color();
+            if p.sprite {
+                let size = ((p.size as f64 * (1.0 - p.age())).ceil() as u32).max(1);
+                self.sprite.set_color_mod(color.r, color.g, color.b);
+                self.sprite.set_alpha_mod(color.a);
+                canvas.copy(
+                    &self.sprite,
+                    None,
+                    Rect::new(
+                        p.position.x as i32 - size as i32 / 2,
+                        p.position.y as i32 - size as i32 / 2,
+                        size,
+                        size,
+                    ),
+                )?;
+            } else {
+                canvas.set_draw_color(color);
+                canvas.fill_rect(Rect::new(
+                    p.position.x as i32,
+                    p.position.y as i32,
+                    p.size,
+                    p.size,
+                ))?;
+            }
+        }
+        canvas.set_blend_mode(BlendMode::None);
+        Ok(())
+    }
+}
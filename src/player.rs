@@ -1,72 +1,78 @@
-use crate::collide;
-use crate::Velocity;
-use sdl2::rect::Rect;
-use sdl2::render::{Canvas, Texture};
-use sdl2::video::Window;
+use crate::collide::Shape;
+use crate::components::{Aim, Health, KeepOnScreen, Sprite, Weapon};
+use crate::game::Sprites;
+use crate::handling::Handling;
+use crate::layers::Layer;
+use crate::projectile::ProjShape;
+use crate::vec2::Vec2;
+use crate::world::{Entity, World};
+use crate::{PLAYER_PROJECTILE_SPEED, SHOOT_DELAY};
+use sdl2::pixels::Color;
+use std::time::Duration;
 
-pub struct Player<'a, 'b> {
-    sprite: &'a Texture<'b>,
-    pub v: Velocity,
-    pub shape: collide::Shape,
-}
-
-impl<'a, 'b> Player<'a, 'b> {
-    pub fn from_sprite(sprite: &'a Texture<'b>) -> Self {
-        Player {
-            sprite,
-            v: Velocity::new(0.0, 0.0),
-            shape: collide::Shape::new_rectangle(
-                crate::WIDTH as i32 / 5,
-                crate::HEIGHT as i32 / 2,
-                sprite.query().width,
-                sprite.query().height,
-            ),
-        }
-    }
+// vertical speed above which the ship banks
+const BANK_SPEED: f64 = 1.0;
 
-    pub fn draw(&self, canvas: &mut Canvas<Window>) -> Result<(), String> {
-        let bounding_box = self.shape.get_box();
-        let rect = Rect::new(
-            bounding_box.x,
-            bounding_box.y,
-            bounding_box.width,
-            bounding_box.height,
-        );
-        canvas.copy(self.sprite, None, rect)
+// Spawn the ship of player number index (from 0), at height y
+pub fn spawn<'a, 'b>(
+    world: &mut World<'a, 'b>,
+    sprites: &Sprites<'a, 'b>,
+    index: usize,
+    y: f64,
+    lives: u32,
+    handling: Handling,
+) -> Entity {
+    let (width, height) = sprites.player_ship.atlas().frame_size("idle");
+    let entity = world.spawn();
+    world.positions.insert(
+        entity,
+        Vec2::new(crate::WIDTH as f64 / 5.0, y - height as f64 / 2.0),
+    );
+    world.velocities.insert(entity, Vec2::ZERO);
+    world
+        .shapes
+        .insert(entity, Shape::new_rectangle(0, 0, width, height));
+    let mut sprite = Sprite::new(sprites.player_ship, "idle");
+    // the second player's ship is orange, so players can tell them apart
+    if index % 2 == 1 {
+        sprite.tint = Some(Color::RGB(0xff, 0xa0, 0x60));
     }
+    world.sprites.insert(entity, sprite);
+    world.healths.insert(entity, Health::new(lives));
+    world.layers.insert(entity, Layer::Player);
+    world.weapons.insert(
+        entity,
+        Weapon {
+            sprite: sprites.player_shot,
+            speed: PLAYER_PROJECTILE_SPEED,
+            cooldown: Duration::from_millis(SHOOT_DELAY),
+            last_shot: None,
+            shots: 1,
+            aim: Aim::Forward,
+            shape: ProjShape::Capsule,
+            layer: Layer::PlayerShot,
+            trigger: false,
+        },
+    );
+    world.handlings.insert(entity, handling);
+    world.keep_on_screen.insert(entity, KeepOnScreen);
+    entity
+}
 
-    pub fn apply_velocity(&mut self) {
-        match &mut self.shape {
-            collide::Shape::Rectangle(collide::Rectangle {
-                x,
-                y,
-                width,
-                height,
-                ..
-            }) => {
-                let max_x = (crate::WIDTH - 1 - *width) as i32 - self.v.x as i32;
-                let max_y = (crate::HEIGHT - 1 - *height) as i32 - self.v.y as i32;
-                if *x >= 0 && *x < max_x {
-                    *x += self.v.x as i32;
-                } else if *x < 0 {
-                    *x = 0;
-                    self.v = Velocity::new(0.0, self.v.y);
-                } else if *x >= max_x {
-                    *x = max_x - 1;
-                    self.v = Velocity::new(0.0, self.v.y);
-                }
-
-                if *y >= 0 && *y < max_y {
-                    *y += self.v.y as i32;
-                } else if *y < 0 {
-                    *y = 0;
-                    self.v = Velocity::new(self.v.x, 0.0);
-                } else if *y >= max_y {
-                    *y = max_y - 1;
-                    self.v = Velocity::new(self.v.x, 0.0);
-                }
-            }
-            _ => {} // TODO Compound
-        }
+// Bank the ship when it's moving up or down
+pub fn bank(world: &mut World, player: Entity) {
+    let vy = match world.velocities.get(player) {
+        Some(v) => v.y,
+        None => return,
+    };
+    let name = if vy < -BANK_SPEED {
+        "bank_up"
+    } else if vy > BANK_SPEED {
+        "bank_down"
+    } else {
+        "idle"
+    };
+    if let Some(sprite) = world.sprites.get_mut(player) {
+        sprite.anim.play(&sprite.sheet.atlas(), name);
     }
 }
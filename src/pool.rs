@@ -0,0 +1,149 @@
+// Slot map with stable handles, used for entities and particles so that
+// spawning and despawning during the game doesn't allocate.
+//
+// All slots are allocated up front. Removing an item puts its slot on a free
+// list, where the next insert picks it up again, so both are O(1). Every slot
+// has a generation that goes up when its item is removed, and handles
+// remember the generation they were made with, so an old handle never finds
+// whatever took its slot afterwards.
+//
+// If more items are alive than the pool was made for, it grows like a Vec,
+// which allocates. Size pools so that never happens during normal play.
+
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Hash)]
+pub struct Handle {
+    index: u32,
+    generation: u32,
+}
+
+impl Handle {
+    // Slot of the item, for storing things alongside the pool
+    pub fn index(self) -> usize {
+        self.index as usize
+    }
+}
+
+#[derive(Clone)]
+struct Slot<T> {
+    generation: u32,
+    item: Option<T>,
+}
+
+pub struct Pool<T> {
+    slots: Vec<Slot<T>>,
+    // slots without an item, the next one to use last
+    free: Vec<u32>,
+    len: usize,
+}
+
+// Copying a pool into another one of the same size doesn't allocate, so
+// snapshots of the game can be taken every frame
+impl<T: Clone> Clone for Pool<T> {
+    fn clone(&self) -> Self {
+        Pool {
+            slots: self.slots.clone(),
+            free: self.free.clone(),
+            len: self.len,
+        }
+    }
+
+    fn clone_from(&mut self, source: &Self) {
+        self.slots.clone_from(&source.slots);
+        self.free.clone_from(&source.free);
+        self.len = source.len;
+    }
+}
+
+impl<T> Pool<T> {
+    pub fn with_capacity(capacity: usize) -> Self {
+        let mut pool = Pool {
+            slots: Vec::with_capacity(capacity),
+            free: Vec::with_capacity(capacity),
+            len: 0,
+        };
+        pool.reserve(capacity);
+        pool
+    }
+
+    // Add empty slots until there are at least capacity of them
+    fn reserve(&mut self, capacity: usize) {
+        let old = self.slots.len();
+        if capacity <= old {
+            return;
+        }
+        self.slots.resize_with(capacity, || Slot {
+            generation: 0,
+            item: None,
+        });
+        // hand out low slots first, so iteration stays near the start
+        self.free.extend((old as u32..capacity as u32).rev());
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    pub fn is_full(&self) -> bool {
+        self.free.is_empty()
+    }
+
+    pub fn insert(&mut self, item: T) -> Handle {
+        if self.free.is_empty() {
+            self.reserve((self.slots.len() * 2).max(1));
+        }
+        let index = self.free.pop().expect("pool has free slots after growing");
+        let slot = &mut self.slots[index as usize];
+        slot.item = Some(item);
+        self.len += 1;
+        Handle {
+            index,
+            generation: slot.generation,
+        }
+    }
+
+    pub fn remove(&mut self, handle: Handle) -> Option<T> {
+        let slot = self.slots.get_mut(handle.index())?;
+        if slot.generation != handle.generation {
+            return None;
+        }
+        let item = slot.item.take()?;
+        slot.generation = slot.generation.wrapping_add(1);
+        self.free.push(handle.index);
+        self.len -= 1;
+        Some(item)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (Handle, &T)> {
+        self.slots.iter().enumerate().filter_map(|(i, slot)| {
+            let handle = Handle {
+                index: i as u32,
+                generation: slot.generation,
+            };
+            slot.item.as_ref().map(|x| (handle, x))
+        })
+    }
+
+    // Remove every item for which keep returns false
+    pub fn retain<F: FnMut(&mut T) -> bool>(&mut self, mut keep: F) {
+        for (i, slot) in self.slots.iter_mut().enumerate() {
+            let remove = match &mut slot.item {
+                Some(item) => !keep(item),
+                None => false,
+            };
+            if remove {
+                slot.item = None;
+                slot.generation = slot.generation.wrapping_add(1);
+                self.free.push(i as u32);
+                self.len -= 1;
+            }
+        }
+    }
+
+    pub fn clear(&mut self) {
+        self.retain(|_| false);
+    }
+}
@@ -1,143 +1,181 @@
-use crate::collide;
-use crate::collide::Shape;
-use crate::player::Player;
-use crate::Velocity;
-use crate::{HEIGHT, WIDTH};
-use sdl2::rect::Rect;
-use sdl2::render::{Canvas, Texture};
-use sdl2::video::Window;
-
-pub struct Projectile<'a, 'b> {
-    v: Velocity,
-    damage: u32,
-    sprite: &'a Texture<'b>,
-    pub shape: Shape,
-    lethal_to: LethalTo,
-}
+use crate::animation::SpriteSheet;
+use crate::collide::{Rectangle, Shape};
+use crate::components::{Health, Sprite};
+use crate::layers::Layer;
+use crate::vec2::Vec2;
+use crate::world::{Entity, World};
 
+// Long shots like the player's laser bolts are capsules, round bullets
+// circles. Either one turns with the shot.
+#[derive(Clone, Copy, Debug)]
 pub enum ProjShape {
-    Rectangle,
+    Capsule,
     Circle,
 }
 
-#[derive(Eq, PartialEq, Debug)]
-pub enum LethalTo {
-    Player,
-    Enemy,
-}
-
 // Get projectile velocity vector which:
-// * points from coords1 to coords2
+// * points from `from` to `to`
 // * has length of magnitude
-fn proj_velocity(coords0: (i32, i32), coords1: (i32, i32), magnitude: f64) -> Velocity {
-    let delta_x: f64 = (coords1.0 - coords0.0) as f64;
-    let delta_y: f64 = (coords1.1 - coords0.1) as f64;
-    let hypotenuse = f64::sqrt((delta_x * delta_x) + (delta_y * delta_y));
-    let mut unit_x: f64 = 0.0;
-    let mut unit_y: f64 = 0.0;
-    if hypotenuse != 0.0 {
-        unit_x = delta_x / hypotenuse;
-        unit_y = delta_y / hypotenuse;
-    }
-    Velocity {
-        x: unit_x * magnitude,
-        y: unit_y * magnitude,
-    }
+fn proj_velocity(from: Vec2, to: Vec2, magnitude: f64) -> Vec2 {
+    (to - from).normalize() * magnitude
 }
 
-impl<'a, 'b> Projectile<'a, 'b> {
-    pub fn from_sprite(
-        sprite: &'a Texture<'b>,
-        ship: &Shape,
-        speed: f64,
-        proj_shape: ProjShape,
-        lethal_to: LethalTo,
-        target: Option<(i32, i32)>,
-    ) -> Self {
-        let bounding_box = ship.get_box();
-        let (x, y) = match ship {
-            Shape::Rectangle(collide::Rectangle { x, y, .. })
-            | Shape::Point(collide::Point { x, y }) => {
-                if speed > 0.0 {
-                    (
-                        x + bounding_box.width as i32,
-                        y + (bounding_box.height / 2) as i32,
-                    )
-                } else {
-                    (*x, y + (bounding_box.height / 2) as i32)
-                }
-            }
-            Shape::Circle(collide::Circle { x, y, r }) => {
-                if speed > 0.0 {
-                    (
-                        x + *r as i32,
-                        y + (bounding_box.height / 2) as i32 - *r as i32,
-                    )
-                } else {
-                    (
-                        x - *r as i32,
-                        y - (bounding_box.height / 2) as i32 - *r as i32,
-                    )
-                }
-            }
-            _ => {
-                if speed > 0.0 {
-                    (
-                        bounding_box.x + bounding_box.width as i32,
-                        bounding_box.y + (bounding_box.height / 2) as i32,
-                    )
-                } else {
-                    (
-                        bounding_box.x,
-                        bounding_box.y - (bounding_box.height / 2) as i32,
-                    )
-                }
-            }
-        };
-        Projectile {
-            v: match target {
-                None => Velocity { x: speed, y: 0.0 },
-                Some(coords) => proj_velocity((x, y), (coords.0, coords.1), speed),
-            },
-            damage: 10,
-            sprite,
-            shape: match proj_shape {
-                ProjShape::Rectangle => {
-                    Shape::new_rectangle(x, y, sprite.query().width, sprite.query().height)
-                }
-                ProjShape::Circle => Shape::new_circle(x, y, sprite.query().width / 2),
-            },
-            lethal_to,
+// Where a shot from `from` flying at speed meets a target now at position,
+// if the target keeps its velocity. None if the shot can't catch it.
+pub fn intercept(from: Vec2, position: Vec2, velocity: Vec2, speed: f64) -> Option<Vec2> {
+    // solve |position + velocity * t - from| = speed * t for the time t
+    let d = position - from;
+    let a = velocity.dot(velocity) - speed * speed;
+    let b = 2.0 * d.dot(velocity);
+    let c = d.dot(d);
+    let t = if a.abs() < 1e-9 {
+        // target exactly as fast as the shot: only works if it's coming closer
+        if b >= 0.0 {
+            return None;
+        }
+        -c / b
+    } else {
+        let discriminant = b * b - 4.0 * a * c;
+        if discriminant < 0.0 {
+            return None;
         }
+        let root = discriminant.sqrt();
+        [(-b - root) / (2.0 * a), (-b + root) / (2.0 * a)]
+            .iter()
+            .cloned()
+            .filter(|&t| t > 0.0)
+            .fold(f64::INFINITY, f64::min)
+    };
+    if t.is_finite() {
+        Some(position + velocity * t)
+    } else {
+        None
     }
+}
 
-    pub fn advance(&mut self) {
-        self.shape.advance(&self.v);
-    }
+// Fire a projectile from the front edge of the ship's bounding box: the right
+// edge for positive speeds, the left edge for negative ones. With a target it
+// flies towards it, turned to face the way it's going, otherwise straight
+// along the x axis.
+pub fn spawn<'a, 'b>(
+    world: &mut World<'a, 'b>,
+    sheet: &'a SpriteSheet<'b>,
+    ship: &Rectangle,
+    speed: f64,
+    proj_shape: ProjShape,
+    layer: Layer,
+    target: Option<Vec2>,
+) -> Entity {
+    let (width, height) = sheet.atlas().frame_size("fly");
+    let x = if speed > 0.0 {
+        ship.x + ship.width as i32
+    } else {
+        ship.x - width as i32
+    };
+    let y = ship.y + (ship.height / 2) as i32 - (height / 2) as i32;
+    let center = Vec2::new(
+        (x + (width / 2) as i32) as f64,
+        (y + (height / 2) as i32) as f64,
+    );
 
-    pub fn draw(&self, canvas: &mut Canvas<Window>) -> Result<(), String> {
-        let bounding_box = self.shape.get_box();
-        canvas.copy(
-            self.sprite,
-            None,
-            Rect::new(
-                bounding_box.x,
-                bounding_box.y,
-                bounding_box.width,
-                bounding_box.height,
+    let entity = world.spawn();
+    world
+        .positions
+        .insert(entity, Vec2::new(x as f64, y as f64));
+    let forward = Vec2::new(speed, 0.0);
+    let velocity = match target {
+        None => forward,
+        Some(target) => proj_velocity(center, target, speed.abs()),
+    };
+    world.velocities.insert(entity, velocity);
+    // the sprite points along the x axis, the way it flies without a target
+    world
+        .orientations
+        .insert(entity, velocity.angle() - forward.angle());
+    world.shapes.insert(
+        entity,
+        match proj_shape {
+            ProjShape::Capsule => Shape::new_capsule(
+                Vec2::new(width as f64 / 2.0, height as f64 / 2.0),
+                width.saturating_sub(height),
+                height / 2,
+                0.0,
+            ),
+            ProjShape::Circle => Shape::new_circle(
+                (width / 2) as i32,
+                (height / 2) as i32,
+                width.min(height) / 2,
             ),
-        )
+        },
+    );
+    world.sprites.insert(entity, Sprite::new(sheet, "fly"));
+    world.layers.insert(entity, layer);
+    world.healths.insert(entity, Health::new(1));
+    entity
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn close(a: Vec2, b: Vec2) -> bool {
+        (a - b).length() < 1e-9
+    }
+
+    // The shot and the target get to the point at the same time
+    fn meet(from: Vec2, position: Vec2, velocity: Vec2, speed: f64, point: Vec2) -> bool {
+        let shot_time = (point - from).length() / speed;
+        close(position + velocity * shot_time, point)
+    }
+
+    #[test]
+    fn stationary_target() {
+        let position = Vec2::new(30.0, 40.0);
+        let point = intercept(Vec2::new(0.0, 0.0), position, Vec2::new(0.0, 0.0), 5.0);
+        assert!(close(point.unwrap(), position));
+    }
+
+    #[test]
+    fn equal_speeds() {
+        let from = Vec2::new(0.0, 0.0);
+        let position = Vec2::new(100.0, 0.0);
+        // coming straight at the shot, they meet halfway
+        let point = intercept(from, position, Vec2::new(-5.0, 0.0), 5.0).unwrap();
+        assert!(close(point, Vec2::new(50.0, 0.0)));
+        // coming closer at an angle
+        let velocity = Vec2::new(-3.0, 4.0);
+        let point = intercept(from, position, velocity, 5.0).unwrap();
+        assert!(meet(from, position, velocity, 5.0, point));
+        // going away, never
+        assert_eq!(intercept(from, position, Vec2::new(5.0, 0.0), 5.0), None);
+        assert_eq!(intercept(from, position, Vec2::new(3.0, 4.0), 5.0), None);
     }
 
-    pub fn is_in_screen(&self) -> bool {
-        self.shape.is_in_screen()
+    #[test]
+    fn target_too_fast_to_catch() {
+        let from = Vec2::new(0.0, 0.0);
+        let position = Vec2::new(100.0, 0.0);
+        assert_eq!(intercept(from, position, Vec2::new(10.0, 0.0), 5.0), None);
+        assert_eq!(intercept(from, position, Vec2::new(6.0, 8.0), 5.0), None);
     }
 
-    pub fn lethal_to_enemy(&self) -> bool {
-        self.lethal_to == LethalTo::Enemy
+    #[test]
+    fn slower_target_going_away() {
+        let from = Vec2::new(0.0, 0.0);
+        let position = Vec2::new(100.0, 0.0);
+        let point = intercept(from, position, Vec2::new(2.0, 0.0), 10.0).unwrap();
+        assert!(close(point, Vec2::new(125.0, 0.0)));
     }
 
-    pub fn lethal_to_player(&self) -> bool {
-        self.lethal_to == LethalTo::Player
+    #[test]
+    fn target_crossing_in_front() {
+        let from = Vec2::new(0.0, 0.0);
+        let position = Vec2::new(100.0, -50.0);
+        let velocity = Vec2::new(0.0, 5.0);
+        let point = intercept(from, position, velocity, 10.0).unwrap();
+        assert!(meet(from, position, velocity, 10.0, point));
+        // aimed ahead of the target, not at it
+        assert!(point.y > position.y);
+        assert!((point.x - 100.0).abs() < 1e-9);
     }
 }
@@ -0,0 +1,291 @@
+use crate::difficulty::Difficulty;
+use crate::game::{Setup, MAX_WEAPON};
+use crate::input::{Input, MAX_PLAYERS};
+use crate::settings::{parse_switch, switch};
+use std::fmt;
+use std::fs;
+use std::path::Path;
+
+// Four hours of frames. Longer recordings stop there, and a file asking for
+// more is refused rather than filling up memory.
+pub const MAX_FRAMES: u64 = 4 * 60 * 60 * 50;
+
+// Everything needed to play a game over exactly as it went: how it started,
+// and every player's input on every frame. The game is deterministic, so
+// feeding it the same inputs gives the same game, as long as the data files
+// in the assets haven't changed. Kept in a text file:
+//
+//   seed <n>
+//   players <n>
+//   weapon <shots>
+//   difficulty <easy|normal|hard>
+//   rank <on|off>
+//   god <on|off>
+//   level <name>
+//   input <frames> <buttons> [<buttons>]
+//
+// followed by the inputs in order. Each input line is a run of frames with
+// the same buttons held, a number per player with a bit per button (see
+// Input::to_bits).
+#[derive(Debug, Clone)]
+pub struct Replay {
+    pub setup: Setup,
+    pub difficulty: Difficulty,
+    pub rank: bool,
+    pub god: bool,
+    pub level: String,
+    // every player's input for the first frame, then the second, and so on
+    inputs: Vec<Input>,
+}
+
+impl Replay {
+    pub fn new(setup: Setup, difficulty: Difficulty, rank: bool, god: bool, level: &str) -> Self {
+        Replay {
+            setup,
+            difficulty,
+            rank,
+            god,
+            level: level.to_string(),
+            inputs: vec![],
+        }
+    }
+
+    pub fn frames(&self) -> usize {
+        self.inputs.len() / self.setup.players
+    }
+
+    // Each player's input on a frame, or None past the end
+    pub fn frame(&self, frame: usize) -> Option<&[Input]> {
+        let players = self.setup.players;
+        self.inputs.get(frame * players..(frame + 1) * players)
+    }
+
+    pub fn is_full(&self) -> bool {
+        self.frames() as u64 >= MAX_FRAMES
+    }
+
+    pub fn push(&mut self, inputs: &[Input]) {
+        self.inputs.extend_from_slice(&inputs[..self.setup.players]);
+    }
+
+    pub fn parse(source: &str) -> Result<Self, String> {
+        let mut replay = Replay::new(
+            Setup {
+                seed: 0,
+                players: 1,
+                weapon: 1,
+            },
+            Difficulty::Normal,
+            false,
+            false,
+            crate::DEFAULT_LEVEL,
+        );
+        let mut seed = None;
+        for (line_no, line) in source.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            replay
+                .parse_line(line, &mut seed)
+                .map_err(|e| format!("line {}: {}", line_no + 1, e))?;
+        }
+        replay.setup.seed = seed.ok_or("no seed")?;
+        Ok(replay)
+    }
+
+    fn parse_line(&mut self, line: &str, seed: &mut Option<u64>) -> Result<(), String> {
+        let words: Vec<&str> = line.split_whitespace().collect();
+        let number = |word: &str| word.parse().map_err(|_| format!("invalid number {}", word));
+        // the inputs depend on how many players there are
+        let started = !self.inputs.is_empty();
+        match words[..] {
+            ["seed", n] => *seed = Some(number(n)?),
+            ["players", n] if !started => match number(n)? {
+                n if (1..=MAX_PLAYERS as u64).contains(&n) => self.setup.players = n as usize,
+                _ => return Err(format!("players go from 1 to {}", MAX_PLAYERS)),
+            },
+            ["weapon", n] => match number(n)? {
+                n if (1..=MAX_WEAPON as u64).contains(&n) => self.setup.weapon = n as u32,
+                _ => return Err(format!("weapons go from 1 to {}", MAX_WEAPON)),
+            },
+            ["difficulty", name] => {
+                self.difficulty = *Difficulty::ALL
+                    .iter()
+                    .find(|x| x.label().eq_ignore_ascii_case(name))
+                    .ok_or_else(|| format!("unknown difficulty {}", name))?
+            }
+            ["rank", value] => self.rank = parse_switch(value)?,
+            ["god", value] => self.god = parse_switch(value)?,
+            ["level", name] => self.level = name.to_string(),
+            ["input", frames, ref buttons @ ..] if buttons.len() == self.setup.players => {
+                let mut inputs = [Input::default(); MAX_PLAYERS];
+                for (input, bits) in inputs.iter_mut().zip(buttons) {
+                    let bits = number(bits)?;
+                    if bits > u64::from(u8::MAX) {
+                        return Err(format!("invalid buttons {}", bits));
+                    }
+                    *input = Input::from_bits(bits as u8);
+                }
+                let frames = number(frames)?;
+                if (self.frames() as u64)
+                    .checked_add(frames)
+                    .is_none_or(|total| total > MAX_FRAMES)
+                {
+                    return Err(format!("longer than {} frames", MAX_FRAMES));
+                }
+                for _ in 0..frames {
+                    self.push(&inputs);
+                }
+            }
+            ["input", ..] => {
+                return Err(format!(
+                    "expected input <frames> and buttons for {} players",
+                    self.setup.players
+                ))
+            }
+            ["players", _] => return Err("players has to come before the inputs".to_string()),
+            _ => return Err("unknown line".to_string()),
+        }
+        Ok(())
+    }
+
+    pub fn load(path: &Path) -> Result<Self, String> {
+        let source = fs::read_to_string(path).map_err(|e| format!("{}: {}", path.display(), e))?;
+        Replay::parse(&source).map_err(|e| format!("{}: {}", path.display(), e))
+    }
+
+    pub fn save(&self, path: &Path) -> Result<(), String> {
+        fs::write(path, self.to_string()).map_err(|e| format!("{}: {}", path.display(), e))
+    }
+}
+
+impl fmt::Display for Replay {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "# Sideways replay, {} frames", self.frames())?;
+        writeln!(f, "seed {}", self.setup.seed)?;
+        writeln!(f, "players {}", self.setup.players)?;
+        writeln!(f, "weapon {}", self.setup.weapon)?;
+        writeln!(f, "difficulty {}", self.difficulty.label().to_lowercase())?;
+        writeln!(f, "rank {}", switch(self.rank))?;
+        writeln!(f, "god {}", switch(self.god))?;
+        writeln!(f, "level {}", self.level)?;
+        let players = self.setup.players;
+        let mut frames = self.inputs.chunks(players).peekable();
+        while let Some(first) = frames.next() {
+            let mut run = 1;
+            while frames.next_if(|x| *x == first).is_some() {
+                run += 1;
+            }
+            write!(f, "input {}", run)?;
+            for input in first {
+                write!(f, " {}", input.to_bits())?;
+            }
+            writeln!(f)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn inputs(bits: &[u8]) -> Vec<Input> {
+        bits.iter().map(|&x| Input::from_bits(x)).collect()
+    }
+
+    fn recorded() -> Replay {
+        let setup = Setup {
+            seed: 42,
+            players: 2,
+            weapon: 3,
+        };
+        let mut replay = Replay::new(setup, Difficulty::Hard, true, false, "level2");
+        for frame in 0..100u8 {
+            replay.push(&inputs(&[frame / 10, 63 - frame / 30]));
+        }
+        replay
+    }
+
+    #[test]
+    fn round_trip() {
+        let replay = recorded();
+        let text = replay.to_string();
+        let parsed = Replay::parse(&text).unwrap();
+        assert_eq!(parsed.setup.seed, 42);
+        assert_eq!(parsed.setup.players, 2);
+        assert_eq!(parsed.setup.weapon, 3);
+        assert_eq!(parsed.difficulty, Difficulty::Hard);
+        assert!(parsed.rank);
+        assert!(!parsed.god);
+        assert_eq!(parsed.level, "level2");
+        assert_eq!(parsed.inputs, replay.inputs);
+        assert_eq!(parsed.to_string(), text);
+    }
+
+    #[test]
+    fn runs_of_the_same_input_share_a_line() {
+        let text = recorded().to_string();
+        let lines = text.lines().filter(|x| x.starts_with("input ")).count();
+        // one player changes every ten frames, and the other every thirty,
+        // at the same time as the first
+        assert_eq!(lines, 10);
+        assert!(text.contains("input 10 0 63\n"));
+    }
+
+    #[test]
+    fn frames() {
+        let replay = Replay::parse("seed 1\nplayers 2\ninput 3 16 0\ninput 1 1 2\n").unwrap();
+        assert_eq!(replay.frames(), 4);
+        assert_eq!(replay.frame(0), Some(&inputs(&[16, 0])[..]));
+        assert_eq!(replay.frame(3), Some(&inputs(&[1, 2])[..]));
+        assert_eq!(replay.frame(4), None);
+    }
+
+    #[test]
+    fn defaults() {
+        let replay = Replay::parse("# just a seed\n\nseed 5\n").unwrap();
+        assert_eq!(replay.setup.players, 1);
+        assert_eq!(replay.difficulty, Difficulty::Normal);
+        assert_eq!(replay.level, crate::DEFAULT_LEVEL);
+        assert_eq!(replay.frames(), 0);
+    }
+
+    #[test]
+    fn bad_files() {
+        let error = |source: &str| Replay::parse(source).unwrap_err();
+        assert_eq!(error("players 1\n"), "no seed");
+        assert_eq!(error("seed x\n"), "line 1: invalid number x");
+        assert!(error("seed 1\nplayers 3\n").starts_with("line 2: players go from"));
+        assert!(error("seed 1\nweapon 0\n").starts_with("line 2: weapons go from"));
+        assert_eq!(
+            error("seed 1\ndifficulty silly\n"),
+            "line 2: unknown difficulty silly"
+        );
+        assert_eq!(
+            error("seed 1\ninput 1 256\n"),
+            "line 2: invalid buttons 256"
+        );
+        assert!(error("seed 1\ninput 1 0 0\n").starts_with("line 2: expected input"));
+        assert_eq!(
+            error("seed 1\ninput 1 0\nplayers 2\n"),
+            "line 3: players has to come before the inputs"
+        );
+        assert_eq!(error("seed 1\nlives 3\n"), "line 2: unknown line");
+    }
+
+    #[test]
+    fn too_long() {
+        let too_long = format!("seed 1\ninput {} 0\n", MAX_FRAMES + 1);
+        assert!(Replay::parse(&too_long).is_err());
+        let max = format!("seed 1\ninput {} 0\ninput 1 0\n", MAX_FRAMES);
+        assert!(Replay::parse(&max).is_err());
+        // adding it to the frames so far would overflow
+        let huge = format!("seed 1\ninput 1 0\ninput {} 0\n", u64::MAX);
+        assert_eq!(
+            Replay::parse(&huge).unwrap_err(),
+            format!("line 3: longer than {} frames", MAX_FRAMES)
+        );
+    }
+}
@@ -0,0 +1,306 @@
+// Rollback netcode for two players over the network (see net.rs).
+//
+// The game is deterministic: the same seed and the same inputs every frame
+// give the same game on both machines, so only inputs are sent. Our own
+// input is used INPUT_DELAY frames after it was read, which gives it that
+// long to reach the other player. When their input for a frame hasn't
+// arrived in time, we guess that they're still holding what they held last
+// and carry on. If the guess turns out wrong, the game is put back the way
+// it was before that frame and the frames since are played again with the
+// real input.
+//
+// To be able to go back, a copy of the game is kept for each of the last
+// MAX_ROLLBACK frames. Rather than guess further ahead than that, the game
+// waits for the other player to catch up.
+
+use crate::audio::Audio;
+use crate::game::Game;
+use crate::input::{Input, MAX_PLAYERS};
+use crate::net::{InputPacket, MAX_PACKET_INPUTS};
+use crate::particles::ParticleSystem;
+
+pub const DEFAULT_INPUT_DELAY: u32 = 2;
+pub const MAX_INPUT_DELAY: u32 = 8;
+// Furthest back a guess can be corrected, in frames
+pub const MAX_ROLLBACK: u32 = 8;
+// Each side can get MAX_ROLLBACK frames past what it has of the other's
+// input, and each side's input is sent delay frames ahead, so the inputs one
+// side is missing can span this many frames. They all have to fit in one
+// packet, or the oldest would never be sent again.
+const _: () = assert!(2 * MAX_ROLLBACK + 2 * MAX_INPUT_DELAY <= MAX_PACKET_INPUTS as u32);
+// Inputs remembered for each player. Enough for the frames that can still be
+// played again, the input delay and a full packet of resends.
+const HISTORY: usize = 64;
+
+pub struct Rollback<'a, 'b> {
+    // which player is on this machine
+    local: usize,
+    delay: u32,
+    // the next frame to play
+    frame: u32,
+    // every player's input, by frame modulo HISTORY. For frames the other
+    // player's input hasn't arrived for yet, the guess that was used.
+    inputs: [[Input; HISTORY]; MAX_PLAYERS],
+    // the other player's input has arrived for every frame before this
+    confirmed: u32,
+    // the other player has our input for every frame before this
+    acknowledged: u32,
+    // earliest frame that was played with a wrong guess
+    mispredicted: Option<u32>,
+    // the game as it was before each of the last few frames, by frame
+    // modulo the number of snapshots
+    snapshots: Vec<Game<'a, 'b>>,
+    // for playing frames again without repeating their sounds
+    silent: Audio,
+    // frames played again during the last update, for the debug overlay
+    resimulated: u32,
+}
+
+impl<'a, 'b> Rollback<'a, 'b> {
+    // Nobody presses anything during the first delay frames, on either side.
+    // Both sides have to use the same delay.
+    pub fn new(local: usize, delay: u32, game: &Game<'a, 'b>) -> Self {
+        let delay = delay.min(MAX_INPUT_DELAY);
+        Rollback {
+            local,
+            delay,
+            frame: 0,
+            inputs: [[Input::default(); HISTORY]; MAX_PLAYERS],
+            confirmed: delay,
+            acknowledged: delay,
+            mispredicted: None,
+            snapshots: vec![game.clone(); MAX_ROLLBACK as usize + 1],
+            silent: Audio::null(),
+            resimulated: 0,
+        }
+    }
+
+    fn remote(&self) -> usize {
+        1 - self.local
+    }
+
+    // Whether every frame played so far was played with the real inputs, so
+    // that what happened in the game is final
+    pub fn is_confirmed(&self) -> bool {
+        self.frame <= self.confirmed
+    }
+
+    pub fn resimulated(&self) -> u32 {
+        self.resimulated
+    }
+
+    // Take in the other player's inputs, noting the first one that differs
+    // from what was guessed
+    pub fn receive(&mut self, packet: &InputPacket) {
+        self.acknowledged = self.acknowledged.max(packet.ack);
+        let remote = self.remote();
+        for (frame, &input) in (packet.first..).zip(packet.inputs()) {
+            // only the next one missing is taken, so there are never gaps.
+            // Anything after a gap arrives again in a later packet.
+            if frame != self.confirmed || frame >= self.frame + HISTORY as u32 / 2 {
+                continue;
+            }
+            let slot = &mut self.inputs[remote][frame as usize % HISTORY];
+            if frame < self.frame && *slot != input {
+                self.mispredicted = Some(self.mispredicted.map_or(frame, |x| x.min(frame)));
+            }
+            *slot = input;
+            self.confirmed += 1;
+        }
+    }
+
+    // Our inputs the other player hasn't acknowledged yet, with our
+    // acknowledgement of theirs
+    pub fn outgoing(&self) -> InputPacket {
+        let known = self.frame + self.delay;
+        let first = self
+            .acknowledged
+            .max(known.saturating_sub(MAX_PACKET_INPUTS as u32));
+        let inputs = (first..known).map(|frame| self.inputs[self.local][frame as usize % HISTORY]);
+        InputPacket::new(self.confirmed, first, inputs)
+    }
+
+    // Correct any wrong guesses, then play the next frame with the local
+    // player's input, unless that would get too far ahead of the other player
+    pub fn update(
+        &mut self,
+        game: &mut Game<'a, 'b>,
+        input: Input,
+        audio: &Audio,
+        particles: &mut ParticleSystem,
+    ) {
+        self.correct(game, particles);
+        if self.frame >= self.confirmed + MAX_ROLLBACK {
+            return;
+        }
+        self.inputs[self.local][(self.frame + self.delay) as usize % HISTORY] = input;
+        self.simulate(game, self.frame, Some(audio), particles);
+        self.frame += 1;
+    }
+
+    // Go back to the first frame played with a wrong guess, and play the
+    // frames since again, silently
+    fn correct(&mut self, game: &mut Game<'a, 'b>, particles: &mut ParticleSystem) {
+        self.resimulated = 0;
+        if let Some(from) = self.mispredicted.take() {
+            game.clone_from(&self.snapshots[from as usize % self.snapshots.len()]);
+            particles.set_emitting(false);
+            for frame in from..self.frame {
+                self.simulate(game, frame, None, particles);
+                self.resimulated += 1;
+            }
+            particles.set_emitting(true);
+        }
+    }
+
+    // Play one frame, keeping a copy of the game from before it. Without
+    // audio it's played silently.
+    fn simulate(
+        &mut self,
+        game: &mut Game<'a, 'b>,
+        frame: u32,
+        audio: Option<&Audio>,
+        particles: &mut ParticleSystem,
+    ) {
+        let slot = frame as usize % self.snapshots.len();
+        self.snapshots[slot].clone_from(game);
+
+        // guess the other player is still holding whatever they held last
+        let remote = self.remote();
+        if frame >= self.confirmed {
+            let last = match self.confirmed {
+                0 => Input::default(),
+                confirmed => self.inputs[remote][(confirmed - 1) as usize % HISTORY],
+            };
+            self.inputs[remote][frame as usize % HISTORY] = last;
+        }
+
+        let i = frame as usize % HISTORY;
+        let inputs = [self.inputs[0][i], self.inputs[1][i]];
+        game.update(&inputs, audio.unwrap_or(&self.silent), particles);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::assets::Assets;
+    use crate::difficulty::Difficulty;
+    use crate::game::{Rules, Setup, Sprites};
+    use crate::handling::Handling;
+    use crate::layers::CollisionMatrix;
+    use crate::{HEIGHT, WIDTH};
+    use std::collections::VecDeque;
+
+    // steps a packet takes to arrive
+    const LATENCY: u32 = 3;
+    const FRAMES: u32 = 1_000;
+
+    // What each player holds, changing every few frames
+    fn script(player: usize, frame: u32) -> Input {
+        Input::from_bits(((frame / 5 + player as u32 * 17) * 7 % 64) as u8)
+    }
+
+    // Whether a packet sent by a player is lost: everything from player 1
+    // for a while, then now and then, and never anything from player 2
+    fn lost(player: usize, step: u32) -> bool {
+        player == 0 && ((100..160).contains(&step) || step % 3 == 0)
+    }
+
+    // Two players on one machine, sending each other packets through queues
+    // instead of sockets, where the first player's connection is bad. Both
+    // games have to come out the same.
+    #[test]
+    fn linked_games_agree() {
+        sdl2::hint::set("SDL_VIDEODRIVER", "dummy");
+        let sdl_context = sdl2::init().unwrap();
+        let window = sdl_context
+            .video()
+            .unwrap()
+            .window("rollback", WIDTH, HEIGHT)
+            .hidden()
+            .build()
+            .unwrap();
+        let canvas = window.into_canvas().software().build().unwrap();
+        let texture_creator = canvas.texture_creator();
+        let mut assets = Assets::new(&texture_creator, Assets::find_dir(None));
+        for name in &["playership", "playershot", "enemy_projectile", "enemyship"] {
+            assets.load_sheet(name).unwrap();
+        }
+        let sprites = Sprites {
+            player_ship: assets.sheet("playership"),
+            player_shot: assets.sheet("playershot"),
+            enemy_ship: assets.sheet("enemyship"),
+            enemy_shot: assets.sheet("enemy_projectile"),
+        };
+        let setup = Setup {
+            seed: 7,
+            players: MAX_PLAYERS,
+            weapon: 1,
+        };
+        let rules = Rules {
+            collision_matrix: CollisionMatrix::default(),
+            handling: Handling::default(),
+            difficulty: Difficulty::Normal,
+            rank: true,
+        };
+        let game = Game::new(sprites, setup, rules);
+        let mut games = [game.clone(), game.clone()];
+        let mut sessions = [
+            Rollback::new(0, DEFAULT_INPUT_DELAY, &game),
+            Rollback::new(1, DEFAULT_INPUT_DELAY, &game),
+        ];
+        let mut particles = [
+            ParticleSystem::new(assets.load_texture("particle.png").unwrap()),
+            ParticleSystem::new(assets.load_texture("particle.png").unwrap()),
+        ];
+        let audio = Audio::null();
+        // packets on their way to each player, with the step they arrive
+        let mut in_flight: [VecDeque<(u32, InputPacket)>; 2] = [VecDeque::new(), VecDeque::new()];
+
+        let mut step = 0;
+        while sessions
+            .iter()
+            .any(|s| s.frame < FRAMES || !s.is_confirmed())
+        {
+            assert!(
+                step < FRAMES * 2,
+                "stalled at frames {} and {}",
+                sessions[0].frame,
+                sessions[1].frame
+            );
+            for player in 0..MAX_PLAYERS {
+                let session = &mut sessions[player];
+                while in_flight[player].front().is_some_and(|x| x.0 <= step) {
+                    session.receive(&in_flight[player].pop_front().unwrap().1);
+                }
+                if session.frame < FRAMES {
+                    let input = script(player, session.frame + session.delay);
+                    session.update(&mut games[player], input, &audio, &mut particles[player]);
+                }
+                if !lost(player, step) {
+                    in_flight[1 - player].push_back((step + LATENCY, session.outgoing()));
+                }
+            }
+            step += 1;
+        }
+        // the last packets can still have corrected a guess
+        for player in 0..MAX_PLAYERS {
+            sessions[player].correct(&mut games[player], &mut particles[player]);
+        }
+
+        for player in 0..MAX_PLAYERS {
+            for frame in FRAMES - HISTORY as u32 / 2..FRAMES {
+                let i = frame as usize % HISTORY;
+                assert_eq!(sessions[0].inputs[player][i], script(player, frame));
+                assert_eq!(sessions[1].inputs[player][i], script(player, frame));
+            }
+            assert_eq!(games[0].score(player), games[1].score(player));
+            assert_eq!(games[0].lives(player), games[1].lives(player));
+        }
+        let counts = [games[0].counts(), games[1].counts()];
+        assert_eq!(counts[0].enemies, counts[1].enemies);
+        assert_eq!(counts[0].projectiles, counts[1].projectiles);
+        assert_eq!(games[0].rank(), games[1].rank());
+    }
+}
@@ -0,0 +1,332 @@
+use crate::files;
+use crate::levels::{Levels, Unlock};
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt;
+use std::fs;
+use std::io::ErrorKind;
+use std::path::{Path, PathBuf};
+
+const FILE_NAME: &str = "save.dat";
+// Version of the format written by this version of the game
+pub const VERSION: u32 = 1;
+// Steps that bring the lines of an older file up to date, applied in turn:
+// the first turns version 1 into version 2, the next 2 into 3, and so on.
+// Add one whenever the format changes and VERSION goes up.
+const MIGRATIONS: [fn(Vec<String>) -> Vec<String>; 0] = [];
+const _: () = assert!(MIGRATIONS.len() + 1 == VERSION as usize);
+
+// Why a save file can't be used
+#[derive(Debug, Clone, PartialEq)]
+pub enum ParseError {
+    // written by a newer version of the game, in this version of the format
+    Newer(u32),
+    Damaged(String),
+}
+
+// Progress kept between runs: which levels are unlocked, the best score in
+// each, and the weapons that have been earned. Kept in a text file
+// in the user's data directory ($XDG_DATA_HOME/sideways, which is usually
+// ~/.local/share/sideways):
+//
+//   version <n>
+//   level <name>
+//   best <level> <score>
+//   weapon <shots>
+//
+// The version comes first. A file from a newer version of the game is left as
+// it is rather than overwritten with less than it had.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SaveData {
+    // unlocked levels, besides the first one, which always is
+    levels: BTreeSet<String>,
+    best_scores: BTreeMap<String, u32>,
+    // starting weapons, by number of shots
+    weapons: BTreeSet<u32>,
+    // the file is from a newer version of the game, and saving over it
+    // would lose whatever that version added
+    read_only: bool,
+}
+
+impl Default for SaveData {
+    fn default() -> Self {
+        SaveData {
+            levels: BTreeSet::new(),
+            best_scores: BTreeMap::new(),
+            weapons: [1].iter().cloned().collect(),
+            read_only: false,
+        }
+    }
+}
+
+impl SaveData {
+    // The save data, and what was wrong with any lines that had to be
+    // skipped. Fails if the version is missing or too new.
+    pub fn parse(source: &str) -> Result<(SaveData, Vec<String>), ParseError> {
+        let mut lines = source
+            .lines()
+            .map(str::trim)
+            .filter(|x| !x.is_empty() && !x.starts_with('#'));
+        let version = match lines
+            .next()
+            .map(|x| x.split_whitespace().collect::<Vec<_>>())
+        {
+            Some(words) if words.len() == 2 && words[0] == "version" => words[1]
+                .parse::<u32>()
+                .ok()
+                .filter(|&x| x > 0)
+                .ok_or_else(|| ParseError::Damaged("invalid version".to_string()))?,
+            _ => {
+                return Err(ParseError::Damaged(
+                    "expected version <n> first".to_string(),
+                ))
+            }
+        };
+        if version > VERSION {
+            return Err(ParseError::Newer(version));
+        }
+        let mut lines: Vec<String> = lines.map(str::to_string).collect();
+        for migrate in &MIGRATIONS[version as usize - 1..] {
+            lines = migrate(lines);
+        }
+
+        let mut save = SaveData::default();
+        let mut errors = vec![];
+        for line in &lines {
+            if let Err(e) = save.parse_line(line) {
+                errors.push(format!("{}: {}", line, e));
+            }
+        }
+        Ok((save, errors))
+    }
+
+    fn parse_line(&mut self, line: &str) -> Result<(), String> {
+        let words: Vec<&str> = line.split_whitespace().collect();
+        match words[..] {
+            ["level", name] => {
+                self.levels.insert(name.to_string());
+            }
+            ["best", level, score] => {
+                let score = score.parse().map_err(|_| "invalid score")?;
+                self.best_scores.insert(level.to_string(), score);
+            }
+            ["weapon", shots] => match shots.parse() {
+                Ok(shots) if (1..=crate::game::MAX_WEAPON).contains(&shots) => {
+                    self.weapons.insert(shots);
+                }
+                _ => return Err("invalid weapon".to_string()),
+            },
+            _ => return Err("unknown line".to_string()),
+        }
+        Ok(())
+    }
+
+    // Where progress is kept, if there's anywhere to keep it
+    pub fn path() -> Option<PathBuf> {
+        files::data_file(FILE_NAME)
+    }
+
+    // Read the save file, starting afresh if there isn't one. A file that
+    // can't be read is moved aside so it isn't lost, except one from a newer
+    // version of the game, which is kept and not saved over.
+    pub fn load() -> SaveData {
+        match SaveData::path() {
+            Some(path) => SaveData::load_from(&path),
+            None => SaveData::default(),
+        }
+    }
+
+    fn load_from(path: &Path) -> SaveData {
+        let source = match fs::read_to_string(path) {
+            Ok(source) => source,
+            Err(e) if e.kind() == ErrorKind::NotFound => return SaveData::default(),
+            Err(e) => {
+                eprintln!("Could not read {}: {}", path.display(), e);
+                SaveData::move_aside(path);
+                return SaveData::default();
+            }
+        };
+        match SaveData::parse(&source) {
+            Ok((save, errors)) => {
+                for e in errors {
+                    eprintln!("{}: skipping {}", path.display(), e);
+                }
+                save
+            }
+            Err(ParseError::Newer(version)) => {
+                eprintln!(
+                    "{} is from a newer version of the game (format {}), progress won't be saved",
+                    path.display(),
+                    version
+                );
+                SaveData {
+                    read_only: true,
+                    ..SaveData::default()
+                }
+            }
+            Err(ParseError::Damaged(e)) => {
+                eprintln!("{}: {}", path.display(), e);
+                SaveData::move_aside(path);
+                SaveData::default()
+            }
+        }
+    }
+
+    fn move_aside(path: &Path) {
+        if let Some(backup) = files::move_aside(path) {
+            eprintln!(
+                "Starting with no progress, the old save was moved to {}",
+                backup.display()
+            );
+        }
+    }
+
+    pub fn save(&self) -> Result<(), String> {
+        self.save_to(&SaveData::path().ok_or("no data directory")?)
+    }
+
+    fn save_to(&self, path: &Path) -> Result<(), String> {
+        if self.read_only {
+            return Ok(());
+        }
+        files::write(path, &self.to_string())
+    }
+
+    pub fn is_unlocked(&self, levels: &Levels, level: &str) -> bool {
+        levels.first().name == level || self.levels.contains(level)
+    }
+
+    pub fn best_score(&self, level: &str) -> Option<u32> {
+        self.best_scores.get(level).cloned()
+    }
+
+    // Shots fired by the best weapon unlocked so far
+    pub fn weapon(&self) -> u32 {
+        self.weapons.iter().cloned().max().unwrap_or(1)
+    }
+
+    // Note a score made in a level, unlocking what it earned. Returns whether
+    // there is anything new to save.
+    pub fn record(&mut self, levels: &Levels, level: &str, score: u32) -> bool {
+        let mut changed = false;
+        if self.best_score(level).is_none_or(|best| score > best) {
+            self.best_scores.insert(level.to_string(), score);
+            changed = true;
+        }
+        let cleared = match levels.get(level) {
+            Some(cleared) if score >= cleared.target => cleared,
+            _ => return changed,
+        };
+        if let Some(next) = levels.after(level) {
+            changed |= self.levels.insert(next.name.clone());
+        }
+        for unlock in &cleared.unlocks {
+            changed |= match unlock {
+                Unlock::Weapon(shots) => self.weapons.insert(*shots),
+            };
+        }
+        changed
+    }
+}
+
+impl fmt::Display for SaveData {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "version {}", VERSION)?;
+        for level in &self.levels {
+            writeln!(f, "level {}", level)?;
+        }
+        for (level, score) in &self.best_scores {
+            writeln!(f, "best {} {}", level, score)?;
+        }
+        for shots in &self.weapons {
+            writeln!(f, "weapon {}", shots)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::files::TempFile;
+
+    fn levels() -> Levels {
+        Levels::parse("level1 3000 weapon 2\nlevel2 6000 weapon 3\nlevel3 10000\n").unwrap()
+    }
+
+    #[test]
+    fn round_trip() {
+        let levels = levels();
+        let mut save = SaveData::default();
+        save.record(&levels, "level1", 3500);
+        save.record(&levels, "level2", 1200);
+        let (loaded, errors) = SaveData::parse(&save.to_string()).unwrap();
+        assert!(errors.is_empty(), "{:?}", errors);
+        assert_eq!(loaded, save);
+        assert_eq!(loaded.best_score("level1"), Some(3500));
+        assert_eq!(loaded.best_score("level2"), Some(1200));
+    }
+
+    #[test]
+    fn record_unlocks_at_target() {
+        let levels = levels();
+        let mut save = SaveData::default();
+        assert!(save.is_unlocked(&levels, "level1"));
+        assert!(!save.is_unlocked(&levels, "level2"));
+
+        assert!(save.record(&levels, "level1", 2999));
+        assert!(!save.is_unlocked(&levels, "level2"));
+        assert_eq!(save.weapon(), 1);
+
+        assert!(save.record(&levels, "level1", 3000));
+        assert!(save.is_unlocked(&levels, "level2"));
+        assert!(!save.is_unlocked(&levels, "level3"));
+        assert_eq!(save.weapon(), 2);
+
+        // a worse score changes nothing
+        assert!(!save.record(&levels, "level1", 100));
+        assert_eq!(save.best_score("level1"), Some(3000));
+    }
+
+    #[test]
+    fn newer_version_stays_read_only() {
+        let source = format!("version {}\nlevel level2\nsomething new\n", VERSION + 1);
+        assert_eq!(
+            SaveData::parse(&source),
+            Err(ParseError::Newer(VERSION + 1))
+        );
+
+        let file = TempFile::new("newer.dat", source.as_bytes());
+        let mut save = SaveData::load_from(&file.0);
+        assert!(save.read_only);
+        save.record(&levels(), "level1", 5000);
+        save.save_to(&file.0).unwrap();
+        assert_eq!(fs::read_to_string(&file.0).unwrap(), source);
+    }
+
+    #[test]
+    fn damaged_file_is_moved_aside() {
+        for source in &["", "level level2\n", "version x\n", "version 0\n"] {
+            assert!(
+                matches!(SaveData::parse(source), Err(ParseError::Damaged(_))),
+                "{:?}",
+                source
+            );
+        }
+
+        let file = TempFile::new("damaged.dat", b"level level2\n");
+        let save = SaveData::load_from(&file.0);
+        assert_eq!(save, SaveData::default());
+        assert!(!file.0.exists());
+        assert!(file.backup().exists());
+    }
+
+    #[test]
+    fn bad_lines_are_skipped() {
+        let source = "version 1\nlevel level2\nbest level1 lots\nweapon 99\nbest level1 42\n";
+        let (save, errors) = SaveData::parse(source).unwrap();
+        assert_eq!(errors.len(), 2);
+        assert!(save.is_unlocked(&levels(), "level2"));
+        assert_eq!(save.best_score("level1"), Some(42));
+        assert_eq!(save.weapon(), 1);
+    }
+}
@@ -0,0 +1,343 @@
+use crate::audio::{DEFAULT_MUSIC_VOLUME, DEFAULT_SFX_VOLUME, MAX_VOLUME};
+use crate::difficulty::Difficulty;
+use crate::display::{WindowMode, MAX_SCALE};
+use crate::files;
+use crate::input::Controls;
+use sdl2::keyboard::Keycode;
+use std::fmt;
+use std::fs;
+use std::io::ErrorKind;
+use std::path::{Path, PathBuf};
+
+const FILE_NAME: &str = "settings.cfg";
+
+// What the player picked in the options menu, and their keys, remembered
+// between runs. Kept in a text file in the user's config directory
+// ($XDG_CONFIG_HOME/sideways, which is usually ~/.config/sideways) with one
+// setting per line:
+//
+//   scale <1 to MAX_SCALE>
+//   display <windowed|borderless|fullscreen>
+//   scanlines <on|off>
+//   sound <0 to MAX_VOLUME>
+//   music <0 to MAX_VOLUME>
+//   difficulty <easy|normal|hard>
+//   rank <on|off>
+//   keys <solo|player1|player2> <up|down|left|right|fire|focus> <key>
+//
+// Keys go by their SDL names, like Left Shift or Space. An action can have
+// several keys, one line each, and the first of those lines replaces the
+// default keys. Settings that are left out, or that can't be read, keep
+// their defaults, so a damaged file never stops the game from starting.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Settings {
+    pub scale: u32,
+    pub mode: WindowMode,
+    pub scanlines: bool,
+    pub sfx_volume: u8,
+    pub music_volume: u8,
+    pub difficulty: Difficulty,
+    pub rank: bool,
+    pub solo: Controls,
+    pub player1: Controls,
+    pub player2: Controls,
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Settings {
+            scale: crate::DEFAULT_SCALE,
+            mode: WindowMode::Windowed,
+            scanlines: false,
+            sfx_volume: DEFAULT_SFX_VOLUME,
+            music_volume: DEFAULT_MUSIC_VOLUME,
+            difficulty: Difficulty::Normal,
+            rank: false,
+            solo: Controls::solo(),
+            player1: Controls::player1(),
+            player2: Controls::player2(),
+        }
+    }
+}
+
+pub fn parse_switch(value: &str) -> Result<bool, String> {
+    match value {
+        "on" => Ok(true),
+        "off" => Ok(false),
+        _ => Err("expected on or off".to_string()),
+    }
+}
+
+pub fn switch(on: bool) -> &'static str {
+    if on {
+        "on"
+    } else {
+        "off"
+    }
+}
+
+fn parse_number(value: &str, max: u32) -> Result<u32, String> {
+    match value.parse::<u32>() {
+        Ok(n) if n <= max => Ok(n),
+        Ok(_) => Err(format!("expected at most {}", max)),
+        Err(_) => Err("invalid number".to_string()),
+    }
+}
+
+impl Settings {
+    // The settings, and what was wrong with any lines that had to be skipped
+    pub fn parse(source: &str) -> (Settings, Vec<String>) {
+        let mut settings = Settings::default();
+        let mut errors = vec![];
+        // actions whose default keys have been replaced
+        let mut rebound = vec![];
+        for (line_no, line) in source.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            if let Err(e) = settings.parse_line(line, &mut rebound) {
+                errors.push(format!("line {}: {}", line_no + 1, e));
+            }
+        }
+        (settings, errors)
+    }
+
+    fn parse_line(&mut self, line: &str, rebound: &mut Vec<String>) -> Result<(), String> {
+        let (name, value) = match line.find(char::is_whitespace) {
+            Some(i) => (&line[..i], line[i..].trim()),
+            None => return Err("expected <setting> <value>".to_string()),
+        };
+        match name {
+            "scale" => match parse_number(value, MAX_SCALE)? {
+                0 => return Err("scale starts at 1".to_string()),
+                scale => self.scale = scale,
+            },
+            "display" => {
+                self.mode = *WindowMode::ALL
+                    .iter()
+                    .find(|x| x.label().eq_ignore_ascii_case(value))
+                    .ok_or_else(|| format!("unknown display mode {}", value))?
+            }
+            "scanlines" => self.scanlines = parse_switch(value)?,
+            "sound" => self.sfx_volume = parse_number(value, MAX_VOLUME.into())? as u8,
+            "music" => self.music_volume = parse_number(value, MAX_VOLUME.into())? as u8,
+            "difficulty" => {
+                self.difficulty = *Difficulty::ALL
+                    .iter()
+                    .find(|x| x.label().eq_ignore_ascii_case(value))
+                    .ok_or_else(|| format!("unknown difficulty {}", value))?
+            }
+            "rank" => self.rank = parse_switch(value)?,
+            "keys" => self.parse_key(value, rebound)?,
+            _ => return Err(format!("unknown setting {}", name)),
+        }
+        Ok(())
+    }
+
+    // <controls> <action> <key>, where the key name can have spaces in it
+    fn parse_key(&mut self, value: &str, rebound: &mut Vec<String>) -> Result<(), String> {
+        let usage = "expected keys <controls> <action> <key>";
+        let mut words = value.splitn(3, char::is_whitespace);
+        let (name, action, key) = match (words.next(), words.next(), words.next()) {
+            (Some(name), Some(action), Some(key)) => (name, action, key.trim()),
+            _ => return Err(usage.to_string()),
+        };
+        let key = Keycode::from_name(key).ok_or_else(|| format!("unknown key {}", key))?;
+        let controls = match name {
+            "solo" => &mut self.solo,
+            "player1" => &mut self.player1,
+            "player2" => &mut self.player2,
+            _ => return Err(format!("unknown controls {}", name)),
+        };
+        let bound = controls
+            .keys_mut(action)
+            .ok_or_else(|| format!("unknown action {}", action))?;
+        let id = format!("{} {}", name, action);
+        if !rebound.contains(&id) {
+            bound.clear();
+            rebound.push(id);
+        }
+        if !bound.contains(&key) {
+            bound.push(key);
+        }
+        Ok(())
+    }
+
+    // Where the settings are kept, if there's anywhere to keep them
+    pub fn path() -> Option<PathBuf> {
+        files::config_file(FILE_NAME)
+    }
+
+    // Read the settings file, falling back to the defaults for anything in it
+    // that's wrong. A file that isn't even text is moved out of the way, so
+    // the next save doesn't lose what's in it.
+    pub fn load() -> Settings {
+        match Settings::path() {
+            Some(path) => Settings::load_from(&path),
+            None => Settings::default(),
+        }
+    }
+
+    fn load_from(path: &Path) -> Settings {
+        match fs::read_to_string(path) {
+            Ok(source) => {
+                let (settings, errors) = Settings::parse(&source);
+                for e in errors {
+                    eprintln!("{}: {}, skipping it", path.display(), e);
+                }
+                settings
+            }
+            Err(e) if e.kind() == ErrorKind::NotFound => Settings::default(),
+            Err(e) => {
+                eprintln!(
+                    "Could not read {}, using the default settings: {}",
+                    path.display(),
+                    e
+                );
+                if let Some(backup) = files::move_aside(path) {
+                    eprintln!("The old settings were moved to {}", backup.display());
+                }
+                Settings::default()
+            }
+        }
+    }
+
+    pub fn save(&self) -> Result<(), String> {
+        self.save_to(&Settings::path().ok_or("no config directory")?)
+    }
+
+    fn save_to(&self, path: &Path) -> Result<(), String> {
+        files::write(path, &self.to_string())
+    }
+}
+
+impl fmt::Display for Settings {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "# Sideways settings, rewritten whenever they change")?;
+        writeln!(f, "scale {}", self.scale)?;
+        writeln!(f, "display {}", self.mode.label().to_lowercase())?;
+        writeln!(f, "scanlines {}", switch(self.scanlines))?;
+        writeln!(f, "sound {}", self.sfx_volume)?;
+        writeln!(f, "music {}", self.music_volume)?;
+        writeln!(f, "difficulty {}", self.difficulty.label().to_lowercase())?;
+        writeln!(f, "rank {}", switch(self.rank))?;
+        for (name, controls) in &[
+            ("solo", &self.solo),
+            ("player1", &self.player1),
+            ("player2", &self.player2),
+        ] {
+            for action in Controls::ACTIONS.iter() {
+                for key in controls.keys(action).unwrap_or_default() {
+                    writeln!(f, "keys {} {} {}", name, action, key.name())?;
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::files::TempFile;
+
+    fn changed() -> Settings {
+        let mut settings = Settings::default();
+        settings.scale = 3;
+        settings.mode = WindowMode::Borderless;
+        settings.scanlines = true;
+        settings.sfx_volume = 0;
+        settings.music_volume = MAX_VOLUME;
+        settings.difficulty = Difficulty::Hard;
+        settings.rank = true;
+        settings.player2.fire = vec![Keycode::Return, Keycode::LCtrl];
+        settings
+    }
+
+    #[test]
+    fn round_trip() {
+        let settings = changed();
+        let (parsed, errors) = Settings::parse(&settings.to_string());
+        assert!(errors.is_empty(), "{:?}", errors);
+        assert_eq!(parsed, settings);
+    }
+
+    #[test]
+    fn missing_settings_keep_their_defaults() {
+        let (settings, errors) = Settings::parse("# nothing yet\n\nmusic 2\n");
+        assert!(errors.is_empty(), "{:?}", errors);
+        assert_eq!(settings.music_volume, 2);
+        assert_eq!(
+            settings,
+            Settings {
+                music_volume: 2,
+                ..Settings::default()
+            }
+        );
+    }
+
+    #[test]
+    fn first_key_replaces_the_defaults() {
+        let source = "keys solo fire Z\nkeys solo fire Left Ctrl\nkeys solo fire Z\n";
+        let (settings, errors) = Settings::parse(source);
+        assert!(errors.is_empty(), "{:?}", errors);
+        assert_eq!(settings.solo.fire, vec![Keycode::Z, Keycode::LCtrl]);
+        assert_eq!(settings.solo.up, Controls::solo().up);
+        assert_eq!(settings.player1, Controls::player1());
+    }
+
+    #[test]
+    fn bad_lines_are_skipped() {
+        let source = "\
+scale 0
+scale 99
+display huge
+scanlines
+sound loud
+music 11
+difficulty silly
+rank maybe
+keys solo jump Space
+keys solo fire Nonsense
+keys nobody fire Space
+keys solo
+volume 3
+sound 4
+";
+        let (settings, errors) = Settings::parse(source);
+        assert_eq!(errors.len(), 13, "{:?}", errors);
+        assert_eq!(errors[0], "line 1: scale starts at 1");
+        assert_eq!(errors[12], "line 13: unknown setting volume");
+        assert_eq!(
+            settings,
+            Settings {
+                sfx_volume: 4,
+                ..Settings::default()
+            }
+        );
+    }
+
+    #[test]
+    fn save_and_load() {
+        let file = TempFile::new("settings.cfg", b"");
+        changed().save_to(&file.0).unwrap();
+        assert_eq!(Settings::load_from(&file.0), changed());
+    }
+
+    #[test]
+    fn missing_file_gives_the_defaults() {
+        let file = TempFile::new("missing.cfg", b"");
+        fs::remove_file(&file.0).unwrap();
+        assert_eq!(Settings::load_from(&file.0), Settings::default());
+        assert!(!file.backup().exists());
+    }
+
+    #[test]
+    fn unreadable_file_is_moved_aside() {
+        let file = TempFile::new("binary.cfg", &[0xff, 0xfe, 0x00, 0x80]);
+        assert_eq!(Settings::load_from(&file.0), Settings::default());
+        assert!(!file.0.exists());
+        assert!(file.backup().exists());
+    }
+}
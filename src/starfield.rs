@@ -1,54 +1,219 @@
-use crate::Velocity;
+use crate::assets::Assets;
+use crate::vec2::Vec2;
 use crate::{HEIGHT, WIDTH};
 use rand::prelude::*;
 use sdl2::pixels::Color;
-use sdl2::rect::Point;
-use sdl2::render::Canvas;
+use sdl2::rect::Rect;
+use sdl2::render::{Canvas, Texture};
 use sdl2::video::Window;
+use std::path::Path;
 
-const MAX_STARS: usize = 128;
+// Stars per layer; each layer's star array is allocated once and reused
+const MAX_STARS: usize = 256;
+const MAX_LAYERS: usize = 8;
 
 #[derive(Clone, Copy, Debug)]
 struct Star {
     pub color: Color,
-    pub x: i32,
-    pub y: i32,
-    pub v: Velocity,
+    pub position: Vec2,
+    pub velocity: Vec2,
+    pub phase: f64, // twinkle phase in radians
+    pub twinkle_rate: f64,
 }
 
-pub struct Starfield {
+#[derive(Clone, Debug)]
+pub struct LayerConfig {
+    pub speed: (f64, f64), // pixels per frame, min and max
+    pub density: usize,    // number of stars on screen
+    pub palette: Vec<Color>,
+    pub size: u32,
+    pub twinkle: f64, // 0.0 for steady stars, 1.0 for stars that fade out completely
+}
+
+struct StarLayer {
+    config: LayerConfig,
     stars: [Option<Star>; MAX_STARS],
 }
 
-impl Starfield {
+// Scrolling image drawn behind the stars, tiled horizontally
+struct ImageLayer<'b> {
+    texture: Texture<'b>,
+    speed: f64,
+    offset: f64,
+}
+
+pub struct Starfield<'b> {
+    images: Vec<ImageLayer<'b>>,
+    layers: Vec<StarLayer>,
+    // asset names this starfield was loaded from
+    files: Vec<String>,
+}
+
+impl<'b> Default for Starfield<'b> {
+    fn default() -> Self {
+        Starfield::new()
+    }
+}
+
+impl<'b> Starfield<'b> {
+    // The classic look: slow dim stars far away, fast bright ones up close
     pub fn new() -> Self {
+        let grey = |v| Color::RGB(v, v, v);
+        Starfield::from_layers(
+            vec![
+                LayerConfig {
+                    speed: (1.0, 2.0),
+                    density: 64,
+                    palette: vec![grey(0x50), grey(0x60)],
+                    size: 1,
+                    twinkle: 0.4,
+                },
+                LayerConfig {
+                    speed: (3.0, 8.0),
+                    density: 48,
+                    palette: vec![grey(0x90), grey(0xa8)],
+                    size: 1,
+                    twinkle: 0.0,
+                },
+                LayerConfig {
+                    speed: (9.0, 15.0),
+                    density: 16,
+                    palette: vec![grey(0xca)],
+                    size: 1,
+                    twinkle: 0.0,
+                },
+            ],
+            vec![],
+            vec![],
+        )
+    }
+
+    fn from_layers(
+        configs: Vec<LayerConfig>,
+        images: Vec<ImageLayer<'b>>,
+        files: Vec<String>,
+    ) -> Self {
         let mut sf = Starfield {
-            stars: [None; MAX_STARS],
+            images,
+            files,
+            layers: configs
+                .into_iter()
+                .take(MAX_LAYERS)
+                .map(|config| StarLayer {
+                    config,
+                    stars: [None; MAX_STARS],
+                })
+                .collect(),
         };
         sf.spawn(true);
         sf
     }
 
+    // Load a starfield definition. The format is one layer per line,
+    // from the farthest to the nearest:
+    //
+    //   stars <min speed> <max speed> <density> <size> <twinkle> <rrggbb> ...
+    //   image <file> <speed>
+    //
+    // Image files are relative to the definition file. Empty lines and lines
+    // starting with # are ignored.
+    pub fn load(assets: &Assets<'b>, path: &str) -> Result<Self, String> {
+        let source = assets.read_to_string(path)?;
+        let dir = Path::new(path).parent().unwrap_or_else(|| Path::new(""));
+        let mut configs = vec![];
+        let mut images = vec![];
+        let mut files = vec![path.to_string()];
+        for (line_no, line) in source.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let err = |msg: &str| format!("{}: line {}: {}", path, line_no + 1, msg);
+            let words: Vec<&str> = line.split_whitespace().collect();
+            let num = |s: &str| s.parse::<f64>().map_err(|_| err("invalid number"));
+            match words[0] {
+                "stars" => {
+                    if words.len() < 7 {
+                        return Err(err(
+                            "expected stars <min speed> <max speed> <density> <size> <twinkle> <colors>",
+                        ));
+                    }
+                    let palette = words[6..]
+                        .iter()
+                        .map(|x| parse_color(x).ok_or_else(|| err("invalid color")))
+                        .collect::<Result<Vec<_>, _>>()?;
+                    let (min, max) = (num(words[1])?, num(words[2])?);
+                    if min <= 0.0 || max < min {
+                        return Err(err("speeds must be positive with min <= max"));
+                    }
+                    configs.push(LayerConfig {
+                        speed: (min, max),
+                        density: (num(words[3])? as usize).min(MAX_STARS),
+                        palette,
+                        size: (num(words[4])? as u32).max(1),
+                        twinkle: num(words[5])?.clamp(0.0, 1.0),
+                    });
+                }
+                "image" => {
+                    if words.len() != 3 {
+                        return Err(err("expected image <file> <speed>"));
+                    }
+                    let image = dir.join(words[1]).to_string_lossy().into_owned();
+                    images.push(ImageLayer {
+                        texture: assets.load_texture(&image)?,
+                        speed: num(words[2])?,
+                        offset: 0.0,
+                    });
+                    files.push(image);
+                }
+                other => return Err(err(&format!("unknown layer type {}", other))),
+            }
+        }
+        Ok(Starfield::from_layers(configs, images, files))
+    }
+
+    // Whether the named asset is part of this starfield
+    pub fn uses(&self, name: &str) -> bool {
+        self.files.iter().any(|x| x == name)
+    }
+
     // Spawn new stars to fill up the starfield
     // If first_frame == true, spawn stars randomly on the x axis
     // as well as the y axis. Otherwise spawn them on the right edge of the screen,
     // i.e. x == WIDTH.
     fn spawn(&mut self, first_frame: bool) {
-        const SPEED_MIN: i32 = 3;
-        const SPEED_MAX: i32 = 15;
-
         let mut rng = thread_rng();
-        for s in self.stars.iter_mut().filter(|x| x.is_none()) {
-            *s = Some(Star {
-                color: Color::RGB(0xca, 0xca, 0xca),
-                v: Velocity::new(-rng.gen_range(SPEED_MIN, SPEED_MAX + 1) as f64, 0.0),
-                x: if first_frame {
-                    rng.gen_range(0, WIDTH as i32)
-                } else {
-                    WIDTH as i32 - 1
-                },
-                y: rng.gen_range(0, HEIGHT as i32),
-            });
+        for layer in self.layers.iter_mut() {
+            let config = &layer.config;
+            for s in layer.stars[..config.density]
+                .iter_mut()
+                .filter(|x| x.is_none())
+            {
+                *s = Some(Star {
+                    color: *config
+                        .palette
+                        .choose(&mut rng)
+                        .unwrap_or(&Color::RGB(0xca, 0xca, 0xca)),
+                    velocity: Vec2::new(
+                        if config.speed.1 > config.speed.0 {
+                            -rng.gen_range(config.speed.0, config.speed.1)
+                        } else {
+                            -config.speed.0
+                        },
+                        0.0,
+                    ),
+                    position: Vec2::new(
+                        if first_frame {
+                            rng.gen_range(0.0, WIDTH as f64)
+                        } else {
+                            WIDTH as f64 - 1.0
+                        },
+                        rng.gen_range(0, HEIGHT as i32) as f64,
+                    ),
+                    phase: rng.gen_range(0.0, std::f64::consts::PI * 2.0),
+                    twinkle_rate: rng.gen_range(0.05, 0.2),
+                });
+            }
         }
     }
 
@@ -57,29 +222,79 @@ impl Starfield {
     }
 
     pub fn advance(&mut self) {
-        for star in self.stars.iter_mut() {
-            match star {
-                None => {}
-                Some(s) => {
-                    // If this star has gone off the left edge of the screen,
-                    // reset it
-                    if s.x < 0 {
-                        *star = None;
-                        continue;
+        for image in self.images.iter_mut() {
+            let width = image.texture.query().width as f64;
+            image.offset = (image.offset + image.speed) % width;
+        }
+        for layer in self.layers.iter_mut() {
+            for star in layer.stars.iter_mut() {
+                match star {
+                    None => {}
+                    Some(s) => {
+                        // If this star has gone off the left edge of the screen,
+                        // reset it
+                        if s.position.x < 0.0 {
+                            *star = None;
+                            continue;
+                        }
+                        s.position += s.velocity;
+                        s.phase += s.twinkle_rate;
                     }
-                    s.x += s.v.x as i32;
                 }
             }
         }
     }
 
     pub fn draw(&self, canvas: &mut Canvas<Window>) -> Result<(), String> {
-        for s in self.stars.iter().filter_map(|&x| x) {
-            canvas.set_draw_color(s.color);
-            if canvas.draw_point(Point::new(s.x, s.y)).is_err() {
-                return Err(String::from("Could not draw stars"));
+        for image in self.images.iter() {
+            let query = image.texture.query();
+            let mut x = -(image.offset as i32);
+            while x < WIDTH as i32 {
+                let mut y = 0;
+                while y < HEIGHT as i32 {
+                    canvas.copy(
+                        &image.texture,
+                        None,
+                        Rect::new(x, y, query.width, query.height),
+                    )?;
+                    y += query.height as i32;
+                }
+                x += query.width as i32;
+            }
+        }
+        for layer in self.layers.iter() {
+            let twinkle = layer.config.twinkle;
+            let size = layer.config.size;
+            for s in layer.stars.iter().filter_map(|&x| x) {
+                let brightness = 1.0 - twinkle * 0.5 * (1.0 + s.phase.sin());
+                let dim = |c: u8| (c as f64 * brightness) as u8;
+                canvas.set_draw_color(Color::RGB(dim(s.color.r), dim(s.color.g), dim(s.color.b)));
+                if canvas
+                    .fill_rect(Rect::new(
+                        s.position.x as i32,
+                        s.position.y as i32,
+                        size,
+                        size,
+                    ))
+                    .is_err()
+                {
+                    return Err(String::from("Could not draw stars"));
+                }
             }
         }
         Ok(())
     }
 }
+
+// Parse a color written as rrggbb
+fn parse_color(hex: &str) -> Option<Color> {
+    if hex.len() != 6 {
+        return None;
+    }
+    let value = u32::from_str_radix(hex, 16).ok()?;
+    Some(Color::RGB(
+        (value >> 16) as u8,
+        (value >> 8) as u8,
+        value as u8,
+    ))
+}
@@ -0,0 +1,325 @@
+use crate::collide::{Collider, Shape};
+use crate::components::Aim;
+use crate::layers::{CollisionMatrix, Contact, Layer};
+use crate::projectile;
+use crate::vec2::Vec2;
+use crate::world::{Entity, World};
+use crate::{FRAME_TIME, HEIGHT, WIDTH};
+use rand::Rng;
+use sdl2::pixels::Color;
+use sdl2::rect::Rect;
+use sdl2::render::Canvas;
+use sdl2::video::Window;
+use std::time::Duration;
+
+// vertical distance between shots of a spread, 100 pixels ahead of the ship
+const SPREAD: f64 = 12.0;
+
+// Buffers the systems reuse from one update to the next, so the game loop
+// stops allocating once they have grown big enough
+#[derive(Default)]
+pub struct Scratch {
+    entities: Vec<Entity>,
+    bodies: Vec<(Entity, Layer, Shape)>,
+    hits: Vec<Hit>,
+}
+
+// Where the ship that enemies aim at is, and where it's going
+#[derive(Debug, Clone, Copy)]
+pub struct Target {
+    pub position: Vec2,
+    pub velocity: Vec2,
+}
+
+// Two entities that ran into each other, and which of them lost a hit point
+#[derive(Debug, Clone, Copy)]
+pub struct Hit {
+    pub a: Entity,
+    pub b: Entity,
+    pub hurt_a: bool,
+    pub hurt_b: bool,
+}
+
+// Move everything by its velocity, stopping the entities that have to stay
+// on screen at the edges
+pub fn movement(world: &mut World) {
+    for (entity, v) in world.velocities.iter_mut() {
+        let position = match world.positions.get_mut(entity) {
+            Some(position) => position,
+            None => continue,
+        };
+        *position += *v;
+        if !world.keep_on_screen.contains(entity) {
+            continue;
+        }
+        let (width, height) = match world.shapes.get(entity) {
+            Some(shape) => {
+                let bounding_box = shape.get_box();
+                (bounding_box.width, bounding_box.height)
+            }
+            None => (0, 0),
+        };
+        let max_x = (WIDTH - 1 - width) as f64;
+        let max_y = (HEIGHT - 1 - height) as f64;
+        if position.x < 0.0 || position.x > max_x {
+            position.x = position.x.clamp(0.0, max_x);
+            v.x = 0.0;
+        }
+        if position.y < 0.0 || position.y > max_y {
+            position.y = position.y.clamp(0.0, max_y);
+            v.y = 0.0;
+        }
+    }
+}
+
+pub fn animate(world: &mut World) {
+    for (_, sprite) in world.sprites.iter_mut() {
+        sprite.anim.advance(&sprite.sheet.atlas(), FRAME_TIME);
+    }
+}
+
+// Fire every weapon whose trigger is held and that has cooled down.
+// Returns the entities that fired.
+pub fn fire<'s, R: Rng>(
+    world: &mut World,
+    scratch: &'s mut Scratch,
+    clock: Duration,
+    targets: &[Target],
+    rng: &mut R,
+) -> &'s [Entity] {
+    let fired = &mut scratch.entities;
+    fired.clear();
+    fired.extend(
+        world
+            .weapons
+            .iter()
+            .filter(|(_, weapon)| {
+                weapon.trigger
+                    && match weapon.last_shot {
+                        Some(last_shot) => clock - last_shot > weapon.cooldown,
+                        None => true,
+                    }
+            })
+            .map(|(entity, _)| entity),
+    );
+
+    fired.retain(|&entity| {
+        // ships hold their fire until they are on screen
+        let shape = match world.world_shape(entity) {
+            Some(shape) if shape.is_in_screen() => shape,
+            _ => return false,
+        };
+        let weapon = match world.weapons.get_mut(entity) {
+            Some(weapon) => {
+                weapon.last_shot = Some(clock);
+                *weapon
+            }
+            None => return false,
+        };
+        let ship = shape.get_box();
+        let (x, y) = shape.center();
+        let center = Vec2::new(x as f64, y as f64);
+        let ahead = if weapon.speed > 0.0 { 100.0 } else { -100.0 };
+        // aim at whoever is closest
+        let player = targets.iter().cloned().min_by(|a, b| {
+            let distance = |x: &Target| (x.position - center).length();
+            distance(a).total_cmp(&distance(b))
+        });
+        for i in 0..weapon.shots as i32 {
+            let target = match (weapon.aim, player) {
+                (Aim::AtPlayer, Some(player)) => Some(player.position),
+                (Aim::Leading, Some(player)) => Some(
+                    projectile::intercept(
+                        center,
+                        player.position,
+                        player.velocity,
+                        weapon.speed.abs(),
+                    )
+                    .unwrap_or(player.position),
+                ),
+                (Aim::Spread(spread), Some(player)) => {
+                    let angle = (rng.gen::<f64>() * 2.0 - 1.0) * spread;
+                    Some(center + (player.position - center).rotate(angle))
+                }
+                (Aim::Angle(angle), _) => Some(center + Vec2::new(ahead, 0.0).rotate(angle)),
+                _ => {
+                    // fan the shots out evenly around straight ahead
+                    let offset = f64::from(2 * i - (weapon.shots as i32 - 1)) * SPREAD / 2.0;
+                    if offset == 0.0 {
+                        None
+                    } else {
+                        Some(center + Vec2::new(ahead, offset))
+                    }
+                }
+            };
+            let shot = projectile::spawn(
+                world,
+                weapon.sprite,
+                &ship,
+                weapon.speed,
+                weapon.shape,
+                weapon.layer,
+                target,
+            );
+            world.owners.insert(shot, entity);
+        }
+        true
+    });
+    fired
+}
+
+// Whether an entity can lose a hit point right now. Entities without health
+// are indestructible, but still get in the way.
+fn can_be_hurt(world: &World, entity: Entity, clock: Duration) -> bool {
+    match world.healths.get(entity) {
+        Some(health) => health.hp > 0 && !health.invincible && clock >= health.invulnerable_until,
+        None => true,
+    }
+}
+
+// Send the entity back the way it came, along the axis where the shapes
+// overlap the least, if it's moving into the other shape
+fn bounce(world: &mut World, entity: Entity, shape: &Shape, other: &Shape) {
+    let (a, b) = (shape.get_box(), other.get_box());
+    let overlap = |a_min: i32, a_len: u32, b_min: i32, b_len: u32| {
+        (a_min + a_len as i32).min(b_min + b_len as i32) - a_min.max(b_min)
+    };
+    let overlap_x = overlap(a.x, a.width, b.x, b.width);
+    let overlap_y = overlap(a.y, a.height, b.y, b.height);
+    let ((ax, ay), (bx, by)) = (shape.center(), other.center());
+    if let Some(v) = world.velocities.get_mut(entity) {
+        if overlap_x < overlap_y {
+            if v.x * f64::from(bx - ax) > 0.0 {
+                v.x = -v.x;
+            }
+        } else if v.y * f64::from(by - ay) > 0.0 {
+            v.y = -v.y;
+        }
+    }
+}
+
+// Find every pair of entities touching each other whose layers interact,
+// and apply what the collision matrix says happens to each of them.
+// checks counts the shape tests made.
+pub fn collisions<'s>(
+    world: &mut World,
+    scratch: &'s mut Scratch,
+    matrix: &CollisionMatrix,
+    clock: Duration,
+    checks: &mut usize,
+) -> &'s [Hit] {
+    let bodies = &mut scratch.bodies;
+    bodies.clear();
+    bodies.extend(
+        world
+            .layers
+            .iter()
+            .filter_map(|(entity, &layer)| world.world_shape(entity).map(|x| (entity, layer, x))),
+    );
+    let hits = &mut scratch.hits;
+    hits.clear();
+    for (i, (a, layer_a, shape_a)) in bodies.iter().enumerate() {
+        for (b, layer_b, shape_b) in &bodies[i + 1..] {
+            let contact_a = matrix.get(*layer_a, *layer_b);
+            let contact_b = matrix.get(*layer_b, *layer_a);
+            if contact_a == Contact::Ignore && contact_b == Contact::Ignore {
+                continue;
+            }
+            // health changes as we go, so something that was just destroyed
+            // can't be hit again, and nothing touches an invulnerable ship
+            if (contact_a == Contact::Hurt && !can_be_hurt(world, *a, clock))
+                || (contact_b == Contact::Hurt && !can_be_hurt(world, *b, clock))
+            {
+                continue;
+            }
+            *checks += 1;
+            if !shape_a.collide(shape_b) {
+                continue;
+            }
+            for &(entity, contact, shape, other) in &[
+                (*a, contact_a, shape_a, shape_b),
+                (*b, contact_b, shape_b, shape_a),
+            ] {
+                match contact {
+                    Contact::Hurt => {
+                        if let Some(health) = world.healths.get_mut(entity) {
+                            health.hp -= 1;
+                        }
+                    }
+                    Contact::Bounce => bounce(world, entity, shape, other),
+                    Contact::Ignore => {}
+                }
+            }
+            hits.push(Hit {
+                a: *a,
+                b: *b,
+                hurt_a: contact_a == Contact::Hurt,
+                hurt_b: contact_b == Contact::Hurt,
+            });
+        }
+    }
+    hits
+}
+
+// Remove destroyed entities, and those that have left the screen
+pub fn despawn(world: &mut World, scratch: &mut Scratch) {
+    let gone = &mut scratch.entities;
+    gone.clear();
+    gone.extend(
+        world
+            .positions
+            .iter()
+            .filter(|&(entity, _)| {
+                let destroyed = world.healths.get(entity).is_some_and(|x| x.hp == 0);
+                let off_screen = !world.keep_on_screen.contains(entity)
+                    && world.world_shape(entity).is_some_and(|x| !x.is_in_screen());
+                destroyed || off_screen
+            })
+            .map(|(entity, _)| entity),
+    );
+    for &entity in gone.iter() {
+        world.despawn(entity);
+    }
+}
+
+// Draw every sprite, ships first and projectiles on top. Entities blink while
+// they're invulnerable.
+pub fn draw(world: &World, canvas: &mut Canvas<Window>, clock: Duration) -> Result<(), String> {
+    let blink_off = clock.subsec_millis() % 200 < 100;
+    for shots in &[false, true] {
+        for (entity, sprite) in world.sprites.iter() {
+            if world.is_shot(entity) != *shots {
+                continue;
+            }
+            let invulnerable = world
+                .healths
+                .get(entity)
+                .is_some_and(|x| clock < x.invulnerable_until);
+            if invulnerable && blink_off {
+                continue;
+            }
+            let position = match world.positions.get(entity) {
+                Some(position) => position,
+                None => continue,
+            };
+            let (width, height) = sprite.size();
+            let dest = Rect::new(position.x as i32, position.y as i32, width, height);
+            // sheets are shared, so put the colours back for the next sprite
+            if let Some(tint) = sprite.tint {
+                sprite.sheet.set_tint(tint);
+            }
+            match world.orientations.get(entity) {
+                Some(&angle) if angle != 0.0 => {
+                    sprite
+                        .sheet
+                        .draw_rotated(canvas, &sprite.anim, dest, angle)?
+                }
+                _ => sprite.sheet.draw(canvas, &sprite.anim, dest)?,
+            }
+            if sprite.tint.is_some() {
+                sprite.sheet.set_tint(Color::RGB(0xff, 0xff, 0xff));
+            }
+        }
+    }
+    Ok(())
+}
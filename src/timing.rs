@@ -0,0 +1,153 @@
+use crate::FRAME_TIME;
+use std::time::{Duration, Instant};
+
+// Number of recent frames the statistics are taken from
+const HISTORY: usize = 120;
+// After a long stall (a breakpoint, dragging the window) don't try to catch up
+// on more updates than this
+const MAX_CATCH_UP: Duration = Duration::from_millis(100);
+pub const MIN_TIME_SCALE: f64 = 0.125;
+pub const MAX_TIME_SCALE: f64 = 4.0;
+
+#[derive(Debug, Clone, Copy)]
+pub struct FrameStats {
+    pub fps: f64,
+    pub average: Duration,
+    pub p50: Duration,
+    pub p95: Duration,
+    pub p99: Duration,
+    pub max: Duration,
+}
+
+// Keeps the game updating at a steady one update per FRAME_TIME, however
+// often frames are actually shown.
+//
+// With vsync, presenting a frame blocks until the display refreshes, and each
+// frame runs however many updates have come due since the previous one.
+// Without it, end_frame() sleeps until the next update is due.
+pub struct FramePacer {
+    vsync: bool,
+    last_frame: Instant,
+    // time that has passed but hasn't been simulated yet
+    accumulator: Duration,
+    // game time passed per real time, for slow motion
+    time_scale: f64,
+    // debug pause: no updates run until resumed or stepped
+    frozen: bool,
+    step: bool,
+    // ring buffer of the most recent frame times
+    frame_times: [Duration; HISTORY],
+    next: usize,
+    count: usize,
+}
+
+impl FramePacer {
+    pub fn new(vsync: bool) -> Self {
+        FramePacer {
+            vsync,
+            // so the first frame runs one update
+            last_frame: Instant::now() - FRAME_TIME,
+            accumulator: Duration::from_secs(0),
+            time_scale: 1.0,
+            frozen: false,
+            step: false,
+            frame_times: [Duration::from_secs(0); HISTORY],
+            next: 0,
+            count: 0,
+        }
+    }
+
+    pub fn vsync(&self) -> bool {
+        self.vsync
+    }
+
+    pub fn time_scale(&self) -> f64 {
+        self.time_scale
+    }
+
+    pub fn set_time_scale(&mut self, time_scale: f64) {
+        self.time_scale = time_scale.clamp(MIN_TIME_SCALE, MAX_TIME_SCALE);
+    }
+
+    pub fn is_frozen(&self) -> bool {
+        self.frozen
+    }
+
+    pub fn toggle_frozen(&mut self) {
+        self.frozen = !self.frozen;
+    }
+
+    // Freeze, and run exactly one update in the next frame
+    pub fn step(&mut self) {
+        self.frozen = true;
+        self.step = true;
+    }
+
+    // Start a new frame. Returns how many fixed updates to run in it.
+    pub fn begin_frame(&mut self) -> u32 {
+        let now = Instant::now();
+        let dt = now - self.last_frame;
+        self.last_frame = now;
+
+        self.frame_times[self.next] = dt;
+        self.next = (self.next + 1) % HISTORY;
+        self.count = (self.count + 1).min(HISTORY);
+
+        if self.frozen {
+            self.accumulator = Duration::from_secs(0);
+            let step = self.step;
+            self.step = false;
+            return if step { 1 } else { 0 };
+        }
+        self.accumulator += dt.min(MAX_CATCH_UP).mul_f64(self.time_scale);
+        let mut updates = 0;
+        while self.accumulator >= FRAME_TIME {
+            self.accumulator -= FRAME_TIME;
+            updates += 1;
+        }
+        updates
+    }
+
+    // Without vsync, sleep until the next update is due
+    pub fn end_frame(&self) {
+        if self.vsync {
+            return;
+        }
+        // the accumulator is in game time, the deadline in real time
+        let deadline = self.last_frame + (FRAME_TIME - self.accumulator).div_f64(self.time_scale);
+        let now = Instant::now();
+        if deadline > now {
+            ::std::thread::sleep(deadline - now);
+        }
+    }
+
+    pub fn stats(&self) -> FrameStats {
+        let mut times = self.frame_times[..self.count].to_vec();
+        times.sort_unstable();
+        let percentile = |p: usize| {
+            if times.is_empty() {
+                Duration::from_secs(0)
+            } else {
+                times[(times.len() - 1) * p / 100]
+            }
+        };
+        let total: Duration = times.iter().sum();
+        let average = if times.is_empty() {
+            Duration::from_secs(0)
+        } else {
+            total / times.len() as u32
+        };
+        FrameStats {
+            fps: if average > Duration::from_secs(0) {
+                1.0 / average.as_secs_f64()
+            } else {
+                0.0
+            },
+            average,
+            p50: percentile(50),
+            p95: percentile(95),
+            p99: percentile(99),
+            max: percentile(100),
+        }
+    }
+}
@@ -0,0 +1,168 @@
+use std::ops::{Add, AddAssign, Mul, Neg, Sub, SubAssign};
+
+// A 2D vector, for positions, velocities and directions. Screen coordinates,
+// so y grows downwards and positive angles turn clockwise on screen.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct Vec2 {
+    pub x: f64,
+    pub y: f64,
+}
+
+// Steering and bullet patterns are built from these, so not all of them are
+// used everywhere yet
+#[allow(dead_code)]
+impl Vec2 {
+    pub const ZERO: Vec2 = Vec2 { x: 0.0, y: 0.0 };
+
+    pub fn new(x: f64, y: f64) -> Self {
+        Vec2 { x, y }
+    }
+
+    // Unit vector pointing at the angle, in radians from the x axis
+    pub fn from_angle(angle: f64) -> Self {
+        Vec2::new(angle.cos(), angle.sin())
+    }
+
+    pub fn scale(self, factor: f64) -> Self {
+        Vec2::new(self.x * factor, self.y * factor)
+    }
+
+    pub fn dot(self, other: Vec2) -> f64 {
+        self.x * other.x + self.y * other.y
+    }
+
+    pub fn length(self) -> f64 {
+        self.x.hypot(self.y)
+    }
+
+    // Same direction with a length of 1, or zero for the zero vector
+    pub fn normalize(self) -> Self {
+        let length = self.length();
+        if length == 0.0 {
+            Vec2::ZERO
+        } else {
+            self.scale(1.0 / length)
+        }
+    }
+
+    pub fn rotate(self, angle: f64) -> Self {
+        let (sin, cos) = angle.sin_cos();
+        Vec2::new(self.x * cos - self.y * sin, self.x * sin + self.y * cos)
+    }
+
+    // t = 0.0 gives self, t = 1.0 gives other
+    pub fn lerp(self, other: Vec2, t: f64) -> Self {
+        self + (other - self).scale(t)
+    }
+
+    // Radians from the x axis, between -PI and PI
+    pub fn angle(self) -> f64 {
+        self.y.atan2(self.x)
+    }
+}
+
+impl Add for Vec2 {
+    type Output = Self;
+    fn add(self, other: Self) -> Self {
+        Vec2::new(self.x + other.x, self.y + other.y)
+    }
+}
+
+impl AddAssign for Vec2 {
+    fn add_assign(&mut self, other: Self) {
+        *self = *self + other;
+    }
+}
+
+impl Sub for Vec2 {
+    type Output = Self;
+    fn sub(self, other: Self) -> Self {
+        Vec2::new(self.x - other.x, self.y - other.y)
+    }
+}
+
+impl SubAssign for Vec2 {
+    fn sub_assign(&mut self, other: Self) {
+        *self = *self - other;
+    }
+}
+
+impl Mul<f64> for Vec2 {
+    type Output = Self;
+    fn mul(self, factor: f64) -> Self {
+        self.scale(factor)
+    }
+}
+
+impl Neg for Vec2 {
+    type Output = Self;
+    fn neg(self) -> Self {
+        Vec2::new(-self.x, -self.y)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::f64::consts::PI;
+
+    fn close(a: Vec2, b: Vec2) -> bool {
+        (a - b).length() < 1e-9
+    }
+
+    #[test]
+    fn arithmetic() {
+        let a = Vec2::new(1.0, 2.0);
+        let b = Vec2::new(3.0, -4.0);
+        assert_eq!(a + b, Vec2::new(4.0, -2.0));
+        assert_eq!(a - b, Vec2::new(-2.0, 6.0));
+        assert_eq!(a * 3.0, Vec2::new(3.0, 6.0));
+        assert_eq!(-a, Vec2::new(-1.0, -2.0));
+        let mut c = a;
+        c += b;
+        c -= a;
+        assert_eq!(c, b);
+    }
+
+    #[test]
+    fn dot_and_length() {
+        assert_eq!(Vec2::new(1.0, 2.0).dot(Vec2::new(3.0, 4.0)), 11.0);
+        assert_eq!(Vec2::new(1.0, 0.0).dot(Vec2::new(0.0, 1.0)), 0.0);
+        assert_eq!(Vec2::new(3.0, 4.0).length(), 5.0);
+    }
+
+    #[test]
+    fn normalize() {
+        assert!(close(Vec2::new(3.0, 4.0).normalize(), Vec2::new(0.6, 0.8)));
+        assert!((Vec2::new(-7.0, 2.0).normalize().length() - 1.0).abs() < 1e-9);
+        assert_eq!(Vec2::ZERO.normalize(), Vec2::ZERO);
+    }
+
+    #[test]
+    fn rotate() {
+        let v = Vec2::new(1.0, 0.0);
+        assert!(close(v.rotate(PI / 2.0), Vec2::new(0.0, 1.0)));
+        assert!(close(v.rotate(PI), Vec2::new(-1.0, 0.0)));
+        assert!(close(v.rotate(PI / 4.0).rotate(-PI / 4.0), v));
+        assert!((Vec2::new(3.0, 4.0).rotate(1.0).length() - 5.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn lerp() {
+        let a = Vec2::new(0.0, 10.0);
+        let b = Vec2::new(10.0, 20.0);
+        assert_eq!(a.lerp(b, 0.0), a);
+        assert_eq!(a.lerp(b, 1.0), b);
+        assert_eq!(a.lerp(b, 0.5), Vec2::new(5.0, 15.0));
+    }
+
+    #[test]
+    fn angle() {
+        assert_eq!(Vec2::new(1.0, 0.0).angle(), 0.0);
+        assert!((Vec2::new(0.0, 1.0).angle() - PI / 2.0).abs() < 1e-9);
+        assert!((Vec2::new(-1.0, 0.0).angle() - PI).abs() < 1e-9);
+        for &angle in &[-2.0, -0.5, 0.0, 1.0, 3.0] {
+            assert!((Vec2::from_angle(angle).angle() - angle).abs() < 1e-9);
+        }
+    }
+}
@@ -0,0 +1,212 @@
+use crate::collide::Shape;
+use crate::components::{Health, KeepOnScreen, Sprite, Weapon};
+use crate::handling::Handling;
+use crate::layers::Layer;
+use crate::pool::{Handle, Pool};
+use crate::vec2::Vec2;
+
+// Entities are pool handles, so one that has been despawned can't be
+// mistaken for whatever reuses its slot
+pub type Entity = Handle;
+
+// Room for this many entities is allocated when the world is made. Enough
+// for a screenful of enemies and bullets; more still work, but allocate.
+const CAPACITY: usize = 1024;
+
+// One kind of component for every entity, indexed by entity slot. Each
+// component remembers which entity it belongs to, so it's never handed out
+// for a stale handle.
+pub struct Storage<T> {
+    items: Vec<Option<(Entity, T)>>,
+}
+
+impl<T: Clone> Clone for Storage<T> {
+    fn clone(&self) -> Self {
+        Storage {
+            items: self.items.clone(),
+        }
+    }
+
+    fn clone_from(&mut self, source: &Self) {
+        self.items.clone_from(&source.items);
+    }
+}
+
+impl<T> Storage<T> {
+    fn with_capacity(capacity: usize) -> Self {
+        let mut items = Vec::with_capacity(capacity);
+        items.resize_with(capacity, || None);
+        Storage { items }
+    }
+
+    pub fn insert(&mut self, entity: Entity, item: T) {
+        if entity.index() >= self.items.len() {
+            self.items.resize_with(entity.index() + 1, || None);
+        }
+        self.items[entity.index()] = Some((entity, item));
+    }
+
+    pub fn remove(&mut self, entity: Entity) -> Option<T> {
+        match self.items.get_mut(entity.index()) {
+            Some(slot) if slot.as_ref().is_some_and(|x| x.0 == entity) => slot.take().map(|x| x.1),
+            _ => None,
+        }
+    }
+
+    pub fn get(&self, entity: Entity) -> Option<&T> {
+        match self.items.get(entity.index()) {
+            Some(Some((owner, item))) if *owner == entity => Some(item),
+            _ => None,
+        }
+    }
+
+    pub fn get_mut(&mut self, entity: Entity) -> Option<&mut T> {
+        match self.items.get_mut(entity.index()) {
+            Some(Some((owner, item))) if *owner == entity => Some(item),
+            _ => None,
+        }
+    }
+
+    pub fn contains(&self, entity: Entity) -> bool {
+        self.get(entity).is_some()
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (Entity, &T)> {
+        self.items
+            .iter()
+            .filter_map(|x| x.as_ref().map(|(entity, x)| (*entity, x)))
+    }
+
+    pub fn iter_mut(&mut self) -> impl Iterator<Item = (Entity, &mut T)> {
+        self.items
+            .iter_mut()
+            .filter_map(|x| x.as_mut().map(|(entity, x)| (*entity, x)))
+    }
+}
+
+// Every entity in the game and all of their components.
+//
+// An entity is just a handle; what it is depends on which components it has.
+// Systems (see systems.rs) go through the entities that have the components
+// they care about. Despawned slots are reused for new entities.
+pub struct World<'a, 'b> {
+    entities: Pool<()>,
+    // top left corner of the entity, in screen pixels
+    pub positions: Storage<Vec2>,
+    // pixels per frame
+    pub velocities: Storage<Vec2>,
+    // collision shapes, relative to the position
+    pub shapes: Storage<Shape>,
+    // radians clockwise that the sprite and shape are turned around the
+    // centre of the shape
+    pub orientations: Storage<f64>,
+    pub sprites: Storage<Sprite<'a, 'b>>,
+    pub healths: Storage<Health>,
+    pub layers: Storage<Layer>,
+    pub weapons: Storage<Weapon<'a, 'b>>,
+    // ships steered by a player
+    pub handlings: Storage<Handling>,
+    // who fired a projectile
+    pub owners: Storage<Entity>,
+    pub keep_on_screen: Storage<KeepOnScreen>,
+}
+
+// clone_from copies into the storages that are already there, so taking a
+// snapshot of the world doesn't allocate
+impl<'a, 'b> Clone for World<'a, 'b> {
+    fn clone(&self) -> Self {
+        let mut world = World::new();
+        world.clone_from(self);
+        world
+    }
+
+    fn clone_from(&mut self, source: &Self) {
+        self.entities.clone_from(&source.entities);
+        self.positions.clone_from(&source.positions);
+        self.velocities.clone_from(&source.velocities);
+        self.shapes.clone_from(&source.shapes);
+        self.orientations.clone_from(&source.orientations);
+        self.sprites.clone_from(&source.sprites);
+        self.healths.clone_from(&source.healths);
+        self.layers.clone_from(&source.layers);
+        self.weapons.clone_from(&source.weapons);
+        self.handlings.clone_from(&source.handlings);
+        self.owners.clone_from(&source.owners);
+        self.keep_on_screen.clone_from(&source.keep_on_screen);
+    }
+}
+
+impl<'a, 'b> Default for World<'a, 'b> {
+    fn default() -> Self {
+        World::new()
+    }
+}
+
+impl<'a, 'b> World<'a, 'b> {
+    pub fn new() -> Self {
+        World {
+            entities: Pool::with_capacity(CAPACITY),
+            positions: Storage::with_capacity(CAPACITY),
+            velocities: Storage::with_capacity(CAPACITY),
+            shapes: Storage::with_capacity(CAPACITY),
+            orientations: Storage::with_capacity(CAPACITY),
+            sprites: Storage::with_capacity(CAPACITY),
+            healths: Storage::with_capacity(CAPACITY),
+            layers: Storage::with_capacity(CAPACITY),
+            weapons: Storage::with_capacity(CAPACITY),
+            handlings: Storage::with_capacity(CAPACITY),
+            owners: Storage::with_capacity(CAPACITY),
+            keep_on_screen: Storage::with_capacity(CAPACITY),
+        }
+    }
+
+    // A new entity without any components
+    pub fn spawn(&mut self) -> Entity {
+        self.entities.insert(())
+    }
+
+    pub fn despawn(&mut self, entity: Entity) {
+        if self.entities.remove(entity).is_none() {
+            return;
+        }
+        self.positions.remove(entity);
+        self.velocities.remove(entity);
+        self.shapes.remove(entity);
+        self.orientations.remove(entity);
+        self.sprites.remove(entity);
+        self.healths.remove(entity);
+        self.layers.remove(entity);
+        self.weapons.remove(entity);
+        self.handlings.remove(entity);
+        self.owners.remove(entity);
+        self.keep_on_screen.remove(entity);
+    }
+
+    // Collision shape moved to where the entity is, and turned the way it faces
+    pub fn world_shape(&self, entity: Entity) -> Option<Shape> {
+        let position = self.positions.get(entity)?;
+        let shape = self
+            .shapes
+            .get(entity)?
+            .offset(position.x as i32, position.y as i32);
+        match self.orientations.get(entity) {
+            Some(&angle) if angle != 0.0 => {
+                let (x, y) = shape.center();
+                Some(shape.rotate(angle, Vec2::new(x as f64, y as f64)))
+            }
+            _ => Some(shape),
+        }
+    }
+
+    pub fn is_shot(&self, entity: Entity) -> bool {
+        self.layers.get(entity).is_some_and(|x| x.is_shot())
+    }
+
+    // Centre of the entity's collision shape, or its position if it has none
+    pub fn center(&self, entity: Entity) -> Option<(i32, i32)> {
+        match self.world_shape(entity) {
+            Some(shape) => Some(shape.center()),
+            None => self.positions.get(entity).map(|p| (p.x as i32, p.y as i32)),
+        }
+    }
+}